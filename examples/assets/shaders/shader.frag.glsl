@@ -6,25 +6,90 @@ layout(location = 0) in vec3 fragNormal;
 layout(location = 1) in vec2 fragCoords_0;
 layout(location = 2) in vec3 fragPosition;
 layout(location = 3) in vec3 fragCameraPosition;
+layout(location = 4) in vec4 fragTangent;
+layout(location = 5) in vec2 fragCoords_1;
+layout(location = 6) in vec4 fragColor_0;
 
 layout(binding = 2) uniform sampler2D textures[100];
 layout(binding = 3) uniform samplerCube irradiance_cubemap;
 layout(binding = 4) uniform samplerCube prefilter_cubemap;
 layout(binding = 5) uniform sampler2D brdflut;
 
-layout(push_constant) uniform Material {
+const int DIRECTIONAL_LIGHT = 0;
+const int POINT_LIGHT = 1;
+const int SPOT_LIGHT = 2;
+
+struct Light {
+  vec3 direction;
+  // Lights with a range of 0 have an infinite range
+  float range;
+  vec3 color;
+  float intensity;
+  vec3 position;
+  int kind;
+  float innerConeCos;
+  float outerConeCos;
+};
+
+layout(std430, binding = 6) readonly buffer Lights {
+  int numberOfLights;
+  Light lights[];
+};
+
+struct TextureInfo {
+  // The index into the texture array or -1 if there is no texture
+  int index;
+  int texCoordSet;
+  // The rows of the KHR_texture_transform matrix
+  vec4 transform[2];
+};
+
+struct Material {
   vec4 baseColorFactor;
   vec3 emissiveFactor;
-  int colorTextureSet;
-  int metallicRoughnessTextureSet;
-  int normalTextureSet;
-  int occlusionTextureSet;
-  int emissiveTextureSet;
+  float emissiveStrength;
   float metallicFactor;
   float roughnessFactor;
-  float alphaMask;
+  int alphaMask;
   float alphaMaskCutoff;
-} material;
+  int unlit;
+  float clearcoatFactor;
+  float clearcoatRoughnessFactor;
+  float sheenRoughnessFactor;
+  vec3 sheenColorFactor;
+  float transmissionFactor;
+  vec3 specularColorFactor;
+  float specularFactor;
+  float ior;
+  float normalScale;
+  float occlusionStrength;
+  float clearcoatNormalScale;
+  TextureInfo colorTexture;
+  TextureInfo metallicRoughnessTexture;
+  TextureInfo normalTexture;
+  TextureInfo occlusionTexture;
+  TextureInfo emissiveTexture;
+  TextureInfo clearcoatTexture;
+  TextureInfo clearcoatRoughnessTexture;
+  TextureInfo clearcoatNormalTexture;
+  TextureInfo sheenColorTexture;
+  TextureInfo sheenRoughnessTexture;
+  TextureInfo transmissionTexture;
+  TextureInfo specularTexture;
+  TextureInfo specularColorTexture;
+};
+
+layout(std430, set = 1, binding = 2) readonly buffer Materials {
+  Material materials[];
+};
+
+// The primitive push constants used by the vertex shader come first
+layout(push_constant) uniform PushConstants {
+  layout(offset = 16) int materialIndex;
+  int textureOffset;
+} pushConstants;
+
+Material material;
 
 layout(location = 0) out vec4 outColor;
 
@@ -76,62 +141,180 @@ vec3 fresnelSchlickRoughness(float cosTheta, vec3 F0, float roughness)
     return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(1.0 - cosTheta, 5.0);
 }
 // ----------------------------------------------------------------------------
-void main()
+float DistributionCharlie(vec3 N, vec3 H, float roughness)
 {
-  vec3 lightPositions[2] = vec3[2](vec3(1.0, -1.0, 1.0),
-                                   vec3(-8.0, -1.0, 0.0));
+    float alpha = max(roughness * roughness, 0.000001);
+    float invAlpha = 1.0 / alpha;
+    float NdotH = max(dot(N, H), 0.0);
+    float sin2h = max(1.0 - NdotH * NdotH, 0.0078125);
 
-  vec3 lightColors[2] = vec3[2](vec3(10.0, 10.0, 10.0),
-                                vec3(10.0, 10.0, 10.0));
+    return (2.0 + invAlpha) * pow(sin2h, invAlpha * 0.5) / (2.0 * PI);
+}
+// ----------------------------------------------------------------------------
+float VisibilityAshikhmin(float NdotL, float NdotV)
+{
+    return clamp(1.0 / (4.0 * (NdotL + NdotV - NdotL * NdotV)), 0.0, 1.0);
+}
+// ----------------------------------------------------------------------------
+float rangeAttenuation(float range, float distance)
+{
+  float inverseSquare = 1.0 / (distance * distance);
+  if (range <= 0.0) {
+    return inverseSquare;
+  }
+  return clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0) * inverseSquare;
+}
+// ----------------------------------------------------------------------------
+float spotAttenuation(Light light, vec3 L)
+{
+  float cosAngle = dot(normalize(light.direction), -L);
+  return smoothstep(light.outerConeCos, light.innerConeCos, cosAngle);
+}
+// ----------------------------------------------------------------------------
+vec4 sampleTexture(TextureInfo info)
+{
+  vec3 coords = vec3(info.texCoordSet == 1 ? fragCoords_1 : fragCoords_0, 1.0);
+  vec2 transformed = vec2(dot(info.transform[0].xyz, coords), dot(info.transform[1].xyz, coords));
+  return texture(textures[pushConstants.textureOffset + info.index], transformed);
+}
+// ----------------------------------------------------------------------------
+vec3 getNormal(TextureInfo normalTexture, float scale)
+{
+  // Back faces of double sided materials are lit from their own side
+  float faceDirection = gl_FrontFacing ? 1.0 : -1.0;
+
+  vec3 N = normalize(fragNormal);
+  if (normalTexture.index < 0 || dot(fragTangent.xyz, fragTangent.xyz) == 0.0) {
+    return N * faceDirection;
+  }
+
+  // Gram-Schmidt orthogonalize the tangent against the interpolated normal
+  vec3 T = normalize(fragTangent.xyz - dot(fragTangent.xyz, N) * N);
+  vec3 B = cross(N, T) * fragTangent.w;
+  mat3 TBN = mat3(T, B, N) * faceDirection;
+  vec3 tangentNormal = sampleTexture(normalTexture).xyz * 2.0 - 1.0;
+  tangentNormal.xy *= scale;
+  return normalize(TBN * tangentNormal);
+}
+// ----------------------------------------------------------------------------
+void main()
+{
+  material = materials[pushConstants.materialIndex];
 
   vec3 albedo = material.baseColorFactor.xyz;
   float baseColorAlpha = material.baseColorFactor.w;
-  if (material.colorTextureSet > -1)
+  if (material.colorTexture.index > -1)
     {
-      vec4 albedoMap = texture(textures[material.colorTextureSet], fragCoords_0);
+      vec4 albedoMap = sampleTexture(material.colorTexture);
       baseColorAlpha = albedoMap.a;
       albedo = pow(albedoMap.rgb, vec3(2.2));
     }
 
+  // Vertex colors are linear and white when the mesh has none
+  albedo *= fragColor_0.rgb;
+  baseColorAlpha *= fragColor_0.a;
+
   if (baseColorAlpha < 0.005) {
     discard;
   }
 
-  float metallic = 1.0;
-  float roughness = 1.0;
-  if (material.metallicRoughnessTextureSet > -1)
+  // Unlit materials skip lighting and display their base color directly
+  if (material.unlit == 1) {
+    outColor = vec4(pow(albedo, vec3(1.0/2.2)), baseColorAlpha);
+    return;
+  }
+
+  // The factors apply on their own when there is no metallic roughness texture
+  float metallic = material.metallicFactor;
+  float roughness = material.roughnessFactor;
+  if (material.metallicRoughnessTexture.index > -1)
     {
-      vec4 physicalDescriptor = texture(textures[material.metallicRoughnessTextureSet], fragCoords_0);
-      metallic = physicalDescriptor.b * material.metallicFactor;
-      roughness = physicalDescriptor.g * material.roughnessFactor;
+      vec4 physicalDescriptor = sampleTexture(material.metallicRoughnessTexture);
+      metallic *= physicalDescriptor.b;
+      roughness *= physicalDescriptor.g;
     }
 
   float ao = 1.0;
-  if (material.occlusionTextureSet > -1)
+  if (material.occlusionTexture.index > -1)
     {
-      vec4 occlusionTexture = texture(textures[material.occlusionTextureSet], fragCoords_0);
-      ao = occlusionTexture.r;
+      vec4 occlusionTexture = sampleTexture(material.occlusionTexture);
+      ao = mix(1.0, occlusionTexture.r, material.occlusionStrength);
     }
 
-  vec3 N = fragNormal;
+  float clearcoat = material.clearcoatFactor;
+  if (material.clearcoatTexture.index > -1) {
+    clearcoat *= sampleTexture(material.clearcoatTexture).r;
+  }
+
+  float clearcoatRoughness = material.clearcoatRoughnessFactor;
+  if (material.clearcoatRoughnessTexture.index > -1) {
+    clearcoatRoughness *= sampleTexture(material.clearcoatRoughnessTexture).g;
+  }
+
+  vec3 sheenColor = material.sheenColorFactor;
+  if (material.sheenColorTexture.index > -1) {
+    sheenColor *= pow(sampleTexture(material.sheenColorTexture).rgb, vec3(2.2));
+  }
+
+  float sheenRoughness = material.sheenRoughnessFactor;
+  if (material.sheenRoughnessTexture.index > -1) {
+    sheenRoughness *= sampleTexture(material.sheenRoughnessTexture).a;
+  }
+
+  // Only dielectrics transmit light
+  float transmission = material.transmissionFactor * (1.0 - metallic);
+  if (material.transmissionTexture.index > -1) {
+    transmission *= sampleTexture(material.transmissionTexture).r;
+  }
+
+  float specularWeight = material.specularFactor;
+  if (material.specularTexture.index > -1) {
+    specularWeight *= sampleTexture(material.specularTexture).a;
+  }
+
+  vec3 specularColor = material.specularColorFactor;
+  if (material.specularColorTexture.index > -1) {
+    specularColor *= pow(sampleTexture(material.specularColorTexture).rgb, vec3(2.2));
+  }
+
+  vec3 N = getNormal(material.normalTexture, material.normalScale);
+  // The clearcoat layer doesn't use the base layer's normal map
+  vec3 clearcoatN = getNormal(material.clearcoatNormalTexture, material.clearcoatNormalScale);
   vec3 V = normalize(fragCameraPosition - fragPosition);
   vec3 R = reflect(-V, N);
 
-  // calculate reflectance at normal incidence; if dia-electric (like plastic) use F0
-  // of 0.04 and if it's a metal, use the albedo color as F0 (metallic workflow)
-  vec3 F0 = vec3(0.04);
+  // calculate reflectance at normal incidence; dielectrics derive F0 from their
+  // index of refraction, which is 0.04 for the default IOR of 1.5, tinted and scaled
+  // by their specular color and weight. Metals use the albedo color as F0 (metallic workflow)
+  float dielectricReflectance = pow((material.ior - 1.0) / (material.ior + 1.0), 2.0);
+  vec3 F0 = min(vec3(dielectricReflectance) * specularColor, vec3(1.0)) * specularWeight;
   F0 = mix(F0, albedo, metallic);
 
+  // Sheen reflects some of the light that would otherwise reach the base layer
+  float sheenAlbedoScaling = 1.0 - max(sheenColor.r, max(sheenColor.g, sheenColor.b)) * 0.157;
+
+  float clearcoatNdotV = max(dot(clearcoatN, V), 0.0);
+
   // reflectance equation
   vec3 Lo = vec3(0.0);
-  for(int i = 0; i < 4; ++i)
+  for(int i = 0; i < numberOfLights; ++i)
     {
+      Light light = lights[i];
+
       // calculate per-light radiance
-      vec3 L = normalize(lightPositions[i] - fragPosition);
+      vec3 L = -normalize(light.direction);
+      float attenuation = 1.0;
+      if (light.kind != DIRECTIONAL_LIGHT) {
+        vec3 pointToLight = light.position - fragPosition;
+        float distance = length(pointToLight);
+        L = pointToLight / distance;
+        attenuation = rangeAttenuation(light.range, distance);
+        if (light.kind == SPOT_LIGHT) {
+          attenuation *= spotAttenuation(light, L);
+        }
+      }
       vec3 H = normalize(V + L);
-      float distance = length(lightPositions[i] - fragPosition);
-      float attenuation = 1.0 / (distance * distance);
-      vec3 radiance = lightColors[i] * attenuation;
+      vec3 radiance = light.color * light.intensity * attenuation;
 
       // Cook-Torrance BRDF
       float NDF = DistributionGGX(N, H, roughness);
@@ -156,8 +339,23 @@ void main()
       // scale light by NdotL
       float NdotL = max(dot(N, L), 0.0);
 
+      // transmitted light doesn't contribute to the diffuse lobe
+      vec3 base = (kD * (1.0 - transmission) * albedo / PI + specular) * radiance * NdotL; // note that we already multiplied the BRDF by the Fresnel (kS) so we won't multiply by kS again
+
+      // Charlie sheen lobe
+      vec3 sheen = sheenColor * DistributionCharlie(N, H, sheenRoughness)
+        * VisibilityAshikhmin(NdotL, max(dot(N, V), 0.0)) * radiance * NdotL;
+
+      // The clearcoat is a dielectric GGX layer with an IOR of 1.5
+      float clearcoatNdotL = max(dot(clearcoatN, L), 0.0);
+      vec3 clearcoatFresnel = fresnelSchlick(max(dot(H, V), 0.0), vec3(0.04));
+      vec3 clearcoatSpecular = DistributionGGX(clearcoatN, H, clearcoatRoughness)
+        * GeometrySmith(clearcoatN, V, L, clearcoatRoughness) * clearcoatFresnel
+        / (4 * clearcoatNdotV * clearcoatNdotL + 0.001);
+
       // add to outgoing radiance Lo
-      Lo += (kD * albedo / PI + specular) * radiance * NdotL; // note that we already multiplied the BRDF by the Fresnel (kS) so we won't multiply by kS again
+      Lo += (base * sheenAlbedoScaling + sheen) * (1.0 - clearcoat * clearcoatFresnel)
+        + clearcoat * clearcoatSpecular * radiance * clearcoatNdotL;
     }
 
   // ambient lighting (we now use IBL as the ambient term)
@@ -170,25 +368,40 @@ void main()
   vec3 irradiance = pow(texture(irradiance_cubemap, N).rgb, vec3(2.2));
   vec3 diffuse      = irradiance * albedo;
 
-  // sample both the pre-filter map and the BRDF lut and combine them together as per the Split-Sum approximation to get the IBL specular part.
+  // Transmitted light is approximated by refracting the view ray into the environment
   const float MAX_REFLECTION_LOD = 4.0;
+  vec3 refracted = refract(-V, N, 1.0 / material.ior);
+  vec3 transmitted = pow(textureLod(prefilter_cubemap, refracted, roughness * MAX_REFLECTION_LOD).rgb, vec3(2.2)) * albedo;
+  diffuse = mix(diffuse, transmitted, transmission);
+
+  // sample both the pre-filter map and the BRDF lut and combine them together as per the Split-Sum approximation to get the IBL specular part.
   vec3 prefilteredColor = pow(textureLod(prefilter_cubemap, R,  roughness * MAX_REFLECTION_LOD).rgb, vec3(2.2));
   vec2 brdf  = texture(brdflut, vec2(max(dot(N, V), 0.0), roughness)).rg;
   vec3 specular = prefilteredColor * (F * brdf.x + brdf.y);
 
-  vec3 ambient = (kD * diffuse + specular) * ao;
+  // Sheen only responds to direct lights, since there is no lookup table for the Charlie BRDF
+  vec3 ambient = (kD * diffuse + specular) * ao * sheenAlbedoScaling;
+
+  vec3 clearcoatR = reflect(-V, clearcoatN);
+  vec3 clearcoatF = fresnelSchlickRoughness(clearcoatNdotV, vec3(0.04), clearcoatRoughness);
+  vec3 clearcoatPrefiltered = pow(textureLod(prefilter_cubemap, clearcoatR, clearcoatRoughness * MAX_REFLECTION_LOD).rgb, vec3(2.2));
+  vec2 clearcoatBrdf = texture(brdflut, vec2(clearcoatNdotV, clearcoatRoughness)).rg;
+  vec3 clearcoatAmbient = clearcoatPrefiltered * (clearcoatF * clearcoatBrdf.x + clearcoatBrdf.y);
+  ambient = ambient * (1.0 - clearcoat * clearcoatF) + clearcoat * clearcoatAmbient * ao;
+
+  // Emission is added before tonemapping so that emissive strengths above one stay bright
+  vec3 emissive = material.emissiveFactor * material.emissiveStrength;
+  if (material.emissiveTexture.index > -1) {
+    vec4 emissiveMap = sampleTexture(material.emissiveTexture);
+    emissive *= pow(emissiveMap.rgb, vec3(2.2));
+  }
 
-  vec3 color = ambient + Lo;
+  vec3 color = ambient + Lo + emissive;
 
   // HDR tonemapping
   color = color / (color + vec3(1.0));
   // gamma correct
   color = pow(color, vec3(1.0/2.2));
 
-  if (material.emissiveTextureSet > -1) {
-    vec4 emissiveMap = texture(textures[material.emissiveTextureSet], fragCoords_0);
-    color += pow(emissiveMap.rgb, vec3(2.2)) * material.emissiveFactor;
-  }
-
   outColor = vec4(color, baseColorAlpha);
 }
@@ -8,6 +8,16 @@ layout(location = 2) in vec2 vCoords_0;
 layout(location = 3) in vec2 vCoords_1;
 layout(location = 4) in vec4 vJoints_0;
 layout(location = 5) in vec4 vWeights_0;
+layout(location = 6) in vec4 vTangent;
+layout(location = 7) in vec4 vColor_0;
+
+#define MAX_MORPH_TARGETS 8
+
+// Per instance, the model matrix occupies locations 8 through 11
+layout(location = 8) in mat4 vInstanceModel;
+layout(location = 12) in vec4 vInstanceMorphTargetWeights[MAX_MORPH_TARGETS / 4];
+// Where the joint matrices of the instance's entity start
+layout(location = 14) in int vInstanceJointOffset;
 
 layout(binding = 0) uniform UboView {
   mat4 view;
@@ -15,28 +25,74 @@ layout(binding = 0) uniform UboView {
   vec3 cameraposition;
 } uboView;
 
-layout(binding = 1) uniform UboInstance {
-  mat4 model;
-} uboInstance;
+// Each vertex has a position, normal, and tangent displacement per morph target
+layout(std430, set = 1, binding = 0) readonly buffer MorphTargets {
+  vec4 displacements[];
+} morphTargets;
 
-layout(push_constant) uniform Constants {
-  vec4 baseColorFactor;
-  int colorTextureSet;
-} constants;
+layout(std430, set = 1, binding = 1) readonly buffer Joints {
+  mat4 jointMatrices[];
+} joints;
+
+layout(push_constant) uniform Primitive {
+  int firstVertex;
+  int morphTargetOffset;
+  int numberOfMorphTargets;
+  int jointOffset;
+} primitive;
 
 layout(location = 0) out vec3 fragNormal;
 layout(location = 1) out vec2 fragCoords_0;
 layout(location = 2) out vec3 fragPosition;
 layout(location = 3) out vec3 fragCameraPosition;
+layout(location = 4) out vec4 fragTangent;
+layout(location = 5) out vec2 fragCoords_1;
+layout(location = 6) out vec4 fragColor_0;
+
+float morphTargetWeight(int target) {
+  return vInstanceMorphTargetWeights[target / 4][target % 4];
+}
 
 void main() {
-  vec4 position = uboInstance.model * vec4(vPosition, 1.0);
+  vec3 morphedPosition = vPosition;
+  vec3 morphedNormal = vNormal;
+  vec3 morphedTangent = vTangent.xyz;
+
+  int numberOfMorphTargets = min(primitive.numberOfMorphTargets, MAX_MORPH_TARGETS);
+  int vertexOffset = primitive.morphTargetOffset +
+    (gl_VertexIndex - primitive.firstVertex) * primitive.numberOfMorphTargets * 3;
+  for (int target = 0; target < numberOfMorphTargets; target++) {
+    int displacementOffset = vertexOffset + target * 3;
+    float weight = morphTargetWeight(target);
+    morphedPosition += weight * morphTargets.displacements[displacementOffset].xyz;
+    morphedNormal += weight * morphTargets.displacements[displacementOffset + 1].xyz;
+    morphedTangent += weight * morphTargets.displacements[displacementOffset + 2].xyz;
+  }
+
+  mat4 model = vInstanceModel;
+  if (primitive.jointOffset >= 0) {
+    int jointOffset = vInstanceJointOffset + primitive.jointOffset;
+    mat4 skinMatrix =
+      vWeights_0.x * joints.jointMatrices[jointOffset + int(vJoints_0.x)] +
+      vWeights_0.y * joints.jointMatrices[jointOffset + int(vJoints_0.y)] +
+      vWeights_0.z * joints.jointMatrices[jointOffset + int(vJoints_0.z)] +
+      vWeights_0.w * joints.jointMatrices[jointOffset + int(vJoints_0.w)];
+    model = model * skinMatrix;
+  }
+
+  vec4 position = model * vec4(morphedPosition, 1.0);
   position.y = -position.y;
 
-  fragNormal = mat3(transpose(inverse(uboInstance.model))) * vNormal;
+  fragNormal = mat3(transpose(inverse(model))) * normalize(morphedNormal);
+  // The handedness of the bitangent is stored in w
+  fragTangent = vec4(mat3(model) * morphedTangent, vTangent.w);
   fragCoords_0 = vCoords_0;
+  fragCoords_1 = vCoords_1;
+  fragColor_0 = vColor_0;
   fragPosition = position.xyz;
   fragCameraPosition = uboView.cameraposition;
 
   gl_Position = uboView.projection * uboView.view * position;
+  // Only used when drawing points
+  gl_PointSize = 1.0;
 }
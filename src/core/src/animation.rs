@@ -0,0 +1,287 @@
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnimationSelection {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationLoopMode {
+    // Plays through once and then returns to the start
+    Once,
+    Loop,
+    // Alternates between playing forwards and backwards
+    PingPong,
+    // Plays through once and then holds the last frame
+    Clamp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationBlendMode {
+    // Blends from the layers below towards this layer's pose
+    Override,
+    // Adds this layer's motion relative to its first frame onto the layers below
+    Additive,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationCrossfade {
+    // The previously playing animation, which keeps playing while it fades out
+    pub from: Box<AnimationState>,
+    pub duration: f32,
+    pub elapsed: f32,
+}
+
+impl AnimationCrossfade {
+    // The weight of the animation being faded in
+    pub fn weight(&self) -> f32 {
+        if self.duration > 0.0 {
+            (self.elapsed / self.duration).min(1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationLayer {
+    pub state: AnimationState,
+    pub weight: f32,
+    pub blend_mode: AnimationBlendMode,
+}
+
+impl AnimationLayer {
+    pub fn new(state: AnimationState, weight: f32, blend_mode: AnimationBlendMode) -> Self {
+        Self {
+            state,
+            weight,
+            blend_mode,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationState {
+    pub animation: AnimationSelection,
+    pub time: f32,
+    pub speed: f32,
+    pub loop_mode: AnimationLoopMode,
+    pub paused: bool,
+    pub finished: bool,
+    pub reversed: bool,
+    pub crossfade: Option<AnimationCrossfade>,
+    // Layers are applied in order on top of this animation
+    pub layers: Vec<AnimationLayer>,
+}
+
+impl Default for AnimationState {
+    fn default() -> Self {
+        Self::new(AnimationSelection::Index(0))
+    }
+}
+
+impl AnimationState {
+    pub fn new(animation: AnimationSelection) -> Self {
+        Self {
+            animation,
+            time: 0.0,
+            speed: 1.0,
+            loop_mode: AnimationLoopMode::Loop,
+            paused: false,
+            finished: false,
+            reversed: false,
+            crossfade: None,
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn play(&mut self, animation: AnimationSelection) {
+        self.animation = animation;
+        self.time = 0.0;
+        self.paused = false;
+        self.finished = false;
+        self.reversed = false;
+        self.crossfade = None;
+    }
+
+    pub fn crossfade(&mut self, animation: AnimationSelection, duration: f32) {
+        let mut from = self.clone();
+        from.crossfade = None;
+        from.layers.clear();
+
+        self.play(animation);
+        if duration > 0.0 {
+            self.crossfade = Some(AnimationCrossfade {
+                from: Box::new(from),
+                duration,
+                elapsed: 0.0,
+            });
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.max(0.0);
+        self.finished = false;
+    }
+
+    pub fn advance(&mut self, delta_time: f32, duration: f32) {
+        if self.paused {
+            return;
+        }
+
+        if let Some(crossfade) = self.crossfade.as_mut() {
+            crossfade.elapsed += delta_time;
+            if crossfade.elapsed >= crossfade.duration {
+                self.crossfade = None;
+            }
+        }
+
+        if self.finished {
+            return;
+        }
+
+        if duration <= 0.0 {
+            self.time = 0.0;
+            return;
+        }
+
+        let direction = if self.reversed { -1.0 } else { 1.0 };
+        self.time += delta_time * self.speed * direction;
+
+        match self.loop_mode {
+            AnimationLoopMode::Once => {
+                if self.time < 0.0 || self.time > duration {
+                    self.time = 0.0;
+                    self.finished = true;
+                }
+            }
+            AnimationLoopMode::Loop => self.time = self.time.rem_euclid(duration),
+            AnimationLoopMode::PingPong => {
+                // Reflect the time back into the animation, changing direction at each end
+                self.time = self.time.rem_euclid(2.0 * duration);
+                if self.time > duration {
+                    self.time = 2.0 * duration - self.time;
+                    self.reversed = !self.reversed;
+                }
+            }
+            AnimationLoopMode::Clamp => {
+                if self.time < 0.0 || self.time > duration {
+                    self.time = self.time.max(0.0).min(duration);
+                    self.finished = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(loop_mode: AnimationLoopMode) -> AnimationState {
+        AnimationState {
+            loop_mode,
+            ..AnimationState::default()
+        }
+    }
+
+    #[test]
+    fn once_returns_to_the_start_when_finished() {
+        let mut state = state(AnimationLoopMode::Once);
+        state.advance(1.5, 2.0);
+        assert_eq!(state.time, 1.5);
+        assert!(!state.finished);
+
+        state.advance(1.0, 2.0);
+        assert_eq!(state.time, 0.0);
+        assert!(state.finished);
+
+        state.advance(1.0, 2.0);
+        assert_eq!(state.time, 0.0);
+    }
+
+    #[test]
+    fn loop_wraps_around() {
+        let mut state = state(AnimationLoopMode::Loop);
+        state.advance(2.5, 2.0);
+        assert_eq!(state.time, 0.5);
+        assert!(!state.finished);
+    }
+
+    #[test]
+    fn loop_wraps_around_when_reversed() {
+        let mut state = state(AnimationLoopMode::Loop);
+        state.reversed = true;
+        state.advance(0.5, 2.0);
+        assert_eq!(state.time, 1.5);
+    }
+
+    #[test]
+    fn ping_pong_changes_direction_at_each_end() {
+        let mut state = state(AnimationLoopMode::PingPong);
+        state.advance(2.5, 2.0);
+        assert_eq!(state.time, 1.5);
+        assert!(state.reversed);
+
+        state.advance(2.0, 2.0);
+        assert_eq!(state.time, 0.5);
+        assert!(!state.reversed);
+    }
+
+    #[test]
+    fn clamp_holds_the_last_frame() {
+        let mut state = state(AnimationLoopMode::Clamp);
+        state.advance(3.0, 2.0);
+        assert_eq!(state.time, 2.0);
+        assert!(state.finished);
+    }
+
+    #[test]
+    fn clamp_holds_the_first_frame_when_reversed() {
+        let mut state = state(AnimationLoopMode::Clamp);
+        state.reversed = true;
+        state.seek(1.0);
+        state.advance(3.0, 2.0);
+        assert_eq!(state.time, 0.0);
+        assert!(state.finished);
+    }
+
+    #[test]
+    fn speed_scales_the_time_step() {
+        let mut state = state(AnimationLoopMode::Loop);
+        state.speed = 0.5;
+        state.advance(1.0, 2.0);
+        assert_eq!(state.time, 0.5);
+    }
+
+    #[test]
+    fn paused_animations_keep_their_time() {
+        let mut state = state(AnimationLoopMode::Loop);
+        state.pause();
+        state.advance(1.0, 2.0);
+        assert_eq!(state.time, 0.0);
+
+        state.resume();
+        state.advance(1.0, 2.0);
+        assert_eq!(state.time, 1.0);
+    }
+
+    #[test]
+    fn crossfades_end_after_their_duration() {
+        let mut state = state(AnimationLoopMode::Loop);
+        state.crossfade(AnimationSelection::Index(1), 1.0);
+        state.advance(0.25, 2.0);
+        let weight = state.crossfade.as_ref().map(AnimationCrossfade::weight);
+        assert_eq!(weight, Some(0.25));
+
+        state.advance(1.0, 2.0);
+        assert!(state.crossfade.is_none());
+    }
+}
@@ -0,0 +1,200 @@
+use nalgebra_glm as glm;
+
+// An axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl BoundingBox {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> Self {
+        Self { min, max }
+    }
+
+    // Returns None if there are no points
+    pub fn from_points<I>(points: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = glm::Vec3>,
+    {
+        points.into_iter().fold(None, |bounding_box, point| {
+            let point_box = Self::new(point, point);
+            Some(bounding_box.map_or(point_box, |bounding_box: Self| {
+                bounding_box.merge(&point_box)
+            }))
+        })
+    }
+
+    pub fn center(&self) -> glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extents(&self) -> glm::Vec3 {
+        self.max - self.min
+    }
+
+    pub fn half_extents(&self) -> glm::Vec3 {
+        self.extents() * 0.5
+    }
+
+    // The radius of the sphere around the center that contains the box
+    pub fn radius(&self) -> f32 {
+        self.half_extents().norm()
+    }
+
+    pub fn merge(&self, other: &Self) -> Self {
+        Self {
+            min: glm::min2(&self.min, &other.min),
+            max: glm::max2(&self.max, &other.max),
+        }
+    }
+
+    pub fn contains_point(&self, point: &glm::Vec3) -> bool {
+        (0..3).all(|axis| self.min[axis] <= point[axis] && point[axis] <= self.max[axis])
+    }
+
+    pub fn corners(&self) -> [glm::Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            glm::vec3(min.x, min.y, min.z),
+            glm::vec3(max.x, min.y, min.z),
+            glm::vec3(min.x, max.y, min.z),
+            glm::vec3(max.x, max.y, min.z),
+            glm::vec3(min.x, min.y, max.z),
+            glm::vec3(max.x, min.y, max.z),
+            glm::vec3(min.x, max.y, max.z),
+            glm::vec3(max.x, max.y, max.z),
+        ]
+    }
+
+    // The box around the transformed corners, which contains the transformed box
+    pub fn transform(&self, transform: &glm::Mat4) -> Self {
+        let corners = self
+            .corners()
+            .iter()
+            .map(|corner| (transform * corner.push(1.0)).xyz())
+            .collect::<Vec<_>>();
+        Self::from_points(corners).expect("Failed to transform a bounding box!")
+    }
+}
+
+// The planes of a view frustum, with their normals facing inwards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [glm::Vec4; 6],
+}
+
+impl Frustum {
+    // Extracts the planes from a view projection matrix with a depth range of zero to one
+    pub fn from_matrix(matrix: &glm::Mat4) -> Self {
+        let row = |index: usize| -> glm::Vec4 { matrix.row(index).transpose() };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [w + x, w - x, w + y, w - y, z, w - z],
+        }
+    }
+
+    // Boxes that are only partly inside count as intersecting
+    pub fn intersects_box(&self, bounding_box: &BoundingBox) -> bool {
+        let (min, max) = (bounding_box.min, bounding_box.max);
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane normal
+            let corner = glm::vec3(
+                if plane.x >= 0.0 { max.x } else { min.x },
+                if plane.y >= 0.0 { max.y } else { min.y },
+                if plane.z >= 0.0 { max.z } else { min.z },
+            );
+            plane.xyz().dot(&corner) + plane.w >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> BoundingBox {
+        BoundingBox::new(glm::vec3(-1.0, -1.0, -1.0), glm::vec3(1.0, 1.0, 1.0))
+    }
+
+    fn frustum() -> Frustum {
+        let projection = glm::perspective_rh_zo(1.0, 90_f32.to_radians(), 0.1, 100.0);
+        Frustum::from_matrix(&projection)
+    }
+
+    fn assert_box_eq(actual: &BoundingBox, expected: &BoundingBox) {
+        assert!(
+            glm::distance(&actual.min, &expected.min) < 1e-5
+                && glm::distance(&actual.max, &expected.max) < 1e-5,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn merge_contains_both_boxes() {
+        let first = BoundingBox::new(glm::vec3(-1.0, 0.0, 2.0), glm::vec3(0.0, 1.0, 3.0));
+        let second = BoundingBox::new(glm::vec3(0.5, -2.0, 2.5), glm::vec3(4.0, 0.5, 2.75));
+        let expected = BoundingBox::new(glm::vec3(-1.0, -2.0, 2.0), glm::vec3(4.0, 1.0, 3.0));
+        assert_eq!(first.merge(&second), expected);
+        assert_eq!(second.merge(&first), expected);
+    }
+
+    #[test]
+    fn transform_translates_and_scales() {
+        let transform =
+            glm::translation(&glm::vec3(1.0, 2.0, 3.0)) * glm::scaling(&glm::vec3(2.0, 1.0, 0.5));
+        let expected = BoundingBox::new(glm::vec3(-1.0, 1.0, 2.5), glm::vec3(3.0, 3.0, 3.5));
+        assert_box_eq(&unit_box().transform(&transform), &expected);
+    }
+
+    #[test]
+    fn transform_contains_the_rotated_box() {
+        let bounding_box = BoundingBox::new(glm::vec3(0.0, 0.0, 0.0), glm::vec3(2.0, 1.0, 1.0));
+        let transform = glm::rotation(90_f32.to_radians(), &glm::vec3(0.0, 0.0, 1.0));
+        let expected = BoundingBox::new(glm::vec3(-1.0, 0.0, 0.0), glm::vec3(0.0, 2.0, 1.0));
+        assert_box_eq(&bounding_box.transform(&transform), &expected);
+    }
+
+    #[test]
+    fn frustum_contains_boxes_in_front_of_the_camera() {
+        let bounding_box = unit_box().transform(&glm::translation(&glm::vec3(0.0, 0.0, -5.0)));
+        assert!(frustum().intersects_box(&bounding_box));
+    }
+
+    #[test]
+    fn frustum_excludes_boxes_behind_the_camera() {
+        let bounding_box = unit_box().transform(&glm::translation(&glm::vec3(0.0, 0.0, 5.0)));
+        assert!(!frustum().intersects_box(&bounding_box));
+    }
+
+    #[test]
+    fn frustum_excludes_boxes_beyond_the_far_plane() {
+        let bounding_box = unit_box().transform(&glm::translation(&glm::vec3(0.0, 0.0, -200.0)));
+        assert!(!frustum().intersects_box(&bounding_box));
+    }
+
+    #[test]
+    fn frustum_excludes_boxes_outside_the_sides() {
+        let frustum = frustum();
+        for offset in [
+            glm::vec3(10.0, 0.0, -5.0),
+            glm::vec3(-10.0, 0.0, -5.0),
+            glm::vec3(0.0, 10.0, -5.0),
+            glm::vec3(0.0, -10.0, -5.0),
+        ]
+        .iter()
+        {
+            let bounding_box = unit_box().transform(&glm::translation(offset));
+            assert!(!frustum.intersects_box(&bounding_box), "{:?}", offset);
+        }
+    }
+
+    #[test]
+    fn frustum_intersects_boxes_partly_inside() {
+        // The box straddles the right plane, which is at x = 5 at this depth
+        let bounding_box = unit_box().transform(&glm::translation(&glm::vec3(5.5, 0.0, -5.0)));
+        assert!(frustum().intersects_box(&bounding_box));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{input::Input, AppState, DeltaTime};
+use crate::{bounds::BoundingBox, input::Input, AppState, DeltaTime};
 use legion::prelude::*;
 use nalgebra_glm as glm;
 use winit::VirtualKeyCode;
@@ -6,6 +6,7 @@ use winit::VirtualKeyCode;
 pub struct CameraState {
     pub view: glm::Mat4,
     pub position: glm::Vec3,
+    pub projection: Projection,
 }
 
 impl Default for CameraState {
@@ -13,10 +14,77 @@ impl Default for CameraState {
         Self {
             view: glm::Mat4::identity(),
             position: glm::Vec3::identity(),
+            projection: Projection::default(),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective {
+        y_fov: f32,
+        z_near: f32,
+        // Perspective projections without a far plane are infinite
+        z_far: Option<f32>,
+    },
+    Orthographic {
+        x_mag: f32,
+        y_mag: f32,
+        z_near: f32,
+        z_far: f32,
+    },
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::Perspective {
+            y_fov: 90_f32.to_radians(),
+            z_near: 0.1,
+            z_far: Some(1000.0),
+        }
+    }
+}
+
+impl Projection {
+    // The aspect ratio of the window is used so that the image isn't stretched
+    pub fn matrix(&self, aspect_ratio: f32) -> glm::Mat4 {
+        match *self {
+            Projection::Perspective {
+                y_fov,
+                z_near,
+                z_far: Some(z_far),
+            } => glm::perspective_zo(aspect_ratio, y_fov, z_near, z_far),
+            Projection::Perspective {
+                y_fov,
+                z_near,
+                z_far: None,
+            } => glm::infinite_perspective_rh_zo(aspect_ratio, y_fov, z_near),
+            Projection::Orthographic {
+                x_mag,
+                y_mag,
+                z_near,
+                z_far,
+            } => glm::ortho_zo(-x_mag, x_mag, -y_mag, y_mag, z_near, z_far),
+        }
+    }
+}
+
+// A camera imported from an asset, which views down the -Z axis of its node
+#[derive(Debug, Clone)]
+pub struct SceneCamera {
+    pub name: Option<String>,
+    pub asset_name: String,
+    pub node_index: usize,
+    pub projection: Projection,
+    // Scene cameras are numbered in the order they are spawned
+    pub index: usize,
+}
+
+// Selects the scene camera that drives the camera state.
+// The app's own camera is used when no scene camera is active.
+#[derive(Debug, Default)]
+pub struct ActiveCamera(pub Option<usize>);
+
 pub enum CameraDirection {
     Forward,
     Backward,
@@ -37,6 +105,13 @@ pub struct Camera {
     pub theta: f32,
     pub phi: f32,
     pub r: f32,
+    // The orbital camera orbits the target at a distance between the radius limits
+    pub target: glm::Vec3,
+    pub min_radius: f32,
+    pub max_radius: f32,
+    pub y_fov: f32,
+    // The radius of the content being viewed, which the clipping planes are fitted to
+    pub scene_radius: f32,
 }
 
 impl Default for Camera {
@@ -54,6 +129,11 @@ impl Default for Camera {
             phi: 105.0_f32.to_radians(),
             r: 5.0,
             sensitivity: 2.0,
+            target: glm::vec3(0.0, 0.0, 0.0),
+            min_radius: 1.0,
+            max_radius: 20.0,
+            y_fov: 90_f32.to_radians(),
+            scene_radius: 20.0,
         }
     }
 }
@@ -83,11 +163,33 @@ impl Camera {
 
     // TODO: separate this out and use a trait for common camera functionality
     pub fn calculate_vectors_orbital(&mut self) {
-        self.position = glm::vec3(
-            self.r * self.phi.sin() * self.theta.sin(),
-            self.r * self.phi.cos(),
-            self.r * self.phi.sin() * self.theta.cos(),
-        );
+        self.position = self.target
+            + glm::vec3(
+                self.r * self.phi.sin() * self.theta.sin(),
+                self.r * self.phi.cos(),
+                self.r * self.phi.sin() * self.theta.cos(),
+            );
+    }
+
+    // Orbits the center of the bounds at a distance where all of it is in view
+    pub fn frame(&mut self, bounding_box: &BoundingBox, aspect_ratio: f32) {
+        let radius = bounding_box.radius().max(0.001);
+        let x_fov = 2.0 * ((self.y_fov * 0.5).tan() * aspect_ratio).atan();
+        let fov = self.y_fov.min(x_fov);
+
+        self.target = bounding_box.center();
+        self.scene_radius = radius;
+        self.r = radius / (fov * 0.5).sin();
+        self.min_radius = radius * 0.01;
+        self.max_radius = self.r * 10.0;
+        self.calculate_vectors_orbital();
+    }
+
+    // Fits the clipping planes around the content, keeping as much depth precision as possible
+    pub fn clip_planes(&self) -> (f32, f32) {
+        let z_far = (self.r + self.scene_radius) * 1.5;
+        let z_near = (self.r - self.scene_radius).max(z_far * 0.001);
+        (z_near, z_far)
     }
 }
 
@@ -95,11 +197,16 @@ pub fn fps_camera_key_system() -> Box<dyn Schedulable> {
     SystemBuilder::new("fps_camera_key")
         .read_resource::<Input>()
         .read_resource::<DeltaTime>()
+        .read_resource::<ActiveCamera>()
         .write_resource::<CameraState>()
         .with_query(<Write<Camera>>::query())
         .build(
-            move |_, mut world, (input, delta_time, camera_state), query| {
-                let cameras = &mut query.iter(&mut world).collect::<Vec<_>>();
+            move |_, world, (input, delta_time, active_camera, camera_state), query| {
+                if active_camera.0.is_some() {
+                    return;
+                }
+
+                let cameras = &mut query.iter(world).collect::<Vec<_>>();
                 let camera = &mut cameras[0];
 
                 let velocity = (camera.speed * delta_time.0 as f32) + 0.002;
@@ -132,44 +239,56 @@ pub fn fps_camera_mouse_system() -> Box<dyn Schedulable> {
     let pitch_threshold = 89.0;
     SystemBuilder::new("fps_camera_mouse")
         .read_resource::<Input>()
+        .read_resource::<ActiveCamera>()
         .write_resource::<CameraState>()
         .with_query(<Write<Camera>>::query())
-        .build(move |_, mut world, (input, camera_state), query| {
-            // TODO: Support multiple cameras
-            let camera = &mut query.iter(&mut world).collect::<Vec<_>>()[0];
+        .build(
+            move |_, world, (input, active_camera, camera_state), query| {
+                if active_camera.0.is_some() {
+                    return;
+                }
 
-            let (x_offset, y_offset) = (
-                (input.mouse.offset_from_center.x as i32) as f32 * camera.sensitivity,
-                (input.mouse.offset_from_center.y as i32) as f32 * camera.sensitivity,
-            );
+                // TODO: Support multiple cameras
+                let camera = &mut query.iter(world).collect::<Vec<_>>()[0];
+
+                let (x_offset, y_offset) = (
+                    (input.mouse.offset_from_center.x as i32) as f32 * camera.sensitivity,
+                    (input.mouse.offset_from_center.y as i32) as f32 * camera.sensitivity,
+                );
 
-            camera.yaw_degrees -= x_offset;
-            camera.pitch_degrees -= y_offset;
+                camera.yaw_degrees -= x_offset;
+                camera.pitch_degrees -= y_offset;
 
-            if camera.pitch_degrees > pitch_threshold {
-                camera.pitch_degrees = pitch_threshold
-            } else if camera.pitch_degrees < -pitch_threshold {
-                camera.pitch_degrees = -pitch_threshold
-            }
+                if camera.pitch_degrees > pitch_threshold {
+                    camera.pitch_degrees = pitch_threshold
+                } else if camera.pitch_degrees < -pitch_threshold {
+                    camera.pitch_degrees = -pitch_threshold
+                }
 
-            camera.calculate_vectors();
+                camera.calculate_vectors();
 
-            let target = camera.position + camera.front;
-            camera_state.view = glm::look_at(&camera.position, &target, &camera.up);
-            camera_state.position = camera.position;
-        })
+                let target = camera.position + camera.front;
+                camera_state.view = glm::look_at(&camera.position, &target, &camera.up);
+                camera_state.position = camera.position;
+            },
+        )
 }
 
 pub fn orbital_camera_mouse_system() -> Box<dyn Schedulable> {
     SystemBuilder::new("orbital_camera_mouse")
         .read_resource::<Input>()
         .read_resource::<AppState>()
+        .read_resource::<ActiveCamera>()
         .write_resource::<CameraState>()
         .with_query(<Write<Camera>>::query())
         .build(
-            move |_, mut world, (input, app_state, camera_state), query| {
+            move |_, world, (input, app_state, active_camera, camera_state), query| {
+                if active_camera.0.is_some() {
+                    return;
+                }
+
                 // TODO: Support multiple cameras
-                let camera = &mut query.iter(&mut world).collect::<Vec<_>>()[0];
+                let camera = &mut query.iter(world).collect::<Vec<_>>()[0];
 
                 if input.mouse.is_left_clicked {
                     let delta = input.mouse.position_delta;
@@ -188,16 +307,21 @@ pub fn orbital_camera_mouse_system() -> Box<dyn Schedulable> {
                     );
                 }
 
-                camera.r -= input.mouse.wheel_delta;
-                camera.r = glm::clamp_scalar(camera.r, 1.0, 20.0);
+                // Zooming is relative to the distance so that it works at any scale
+                camera.r -= input.mouse.wheel_delta * camera.r * 0.1;
+                camera.r = glm::clamp_scalar(camera.r, camera.min_radius, camera.max_radius);
 
                 camera.calculate_vectors_orbital();
-                camera_state.view = glm::look_at(
-                    &camera.position,
-                    &glm::vec3(0.0, 0.0, 0.0),
-                    &camera.world_up,
-                );
+                camera_state.view =
+                    glm::look_at(&camera.position, &camera.target, &camera.world_up);
                 camera_state.position = camera.position;
+
+                let (z_near, z_far) = camera.clip_planes();
+                camera_state.projection = Projection::Perspective {
+                    y_fov: camera.y_fov,
+                    z_near,
+                    z_far: Some(z_far),
+                };
             },
         )
 }
@@ -1,8 +1,22 @@
+use legion::prelude::Entity;
 use nalgebra_glm as glm;
 
 #[derive(Debug)]
 pub struct AssetName(pub String); // TODO: Make this a key instead of a full path
 
+// Marks lights and cameras spawned from the asset of another entity,
+// so they can be removed along with it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AssetChild(pub Entity);
+
+// The node of the parent's asset that a spawned light follows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AssetNode(pub usize);
+
+// Marks the entities that the camera frames, instead of every asset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selected;
+
 #[derive(Debug)]
 pub struct Transform {
     pub translate: glm::Mat4,
@@ -19,3 +33,35 @@ impl Default for Transform {
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    // Cone angles are in radians from the spot direction
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+// Lights shine down the -Z axis of their entity's transform
+#[derive(Debug, Clone)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: glm::Vec3,
+    pub intensity: f32,
+    // Lights without a range have an infinite range
+    pub range: Option<f32>,
+}
+
+impl Light {
+    pub fn new(kind: LightKind, color: glm::Vec3, intensity: f32) -> Self {
+        Self {
+            kind,
+            color,
+            intensity,
+            range: None,
+        }
+    }
+}
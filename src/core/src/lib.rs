@@ -1,3 +1,5 @@
+pub mod animation;
+pub mod bounds;
 pub mod camera;
 pub mod components;
 pub mod input;
@@ -15,8 +17,54 @@ pub struct Window {
 
 pub struct DeltaTime(pub f64);
 
-pub struct AnimationState {
-    pub time: f32,
+// Assets being loaded in the background, for showing loading progress
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct AssetLoadingProgress {
+    pub loading: Vec<String>,
+    pub failed: Vec<String>,
+    pub loaded: usize,
+}
+
+impl AssetLoadingProgress {
+    pub fn is_loading(&self) -> bool {
+        !self.loading.is_empty()
+    }
+
+    // Failed assets count as finished
+    pub fn fraction(&self) -> f32 {
+        let finished = self.loaded + self.failed.len();
+        let total = finished + self.loading.len();
+        if total == 0 {
+            1.0
+        } else {
+            finished as f32 / total as f32
+        }
+    }
+}
+
+// What the renderer currently holds in memory, for inspecting the asset caches
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssetCacheStats {
+    pub assets: usize,
+    pub textures: usize,
+    pub materials: usize,
+    pub environments: usize,
+    pub samplers: usize,
+}
+
+// How much of the scene was culled against the camera frustum in the last frame
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CullingStats {
+    pub mesh_instances: usize,
+    pub visible_mesh_instances: usize,
+    // Draws of primitives with at least one visible instance
+    pub draws: usize,
+}
+
+impl CullingStats {
+    pub fn culled_mesh_instances(&self) -> usize {
+        self.mesh_instances - self.visible_mesh_instances
+    }
 }
 
 /// # Safety
@@ -1,19 +1,26 @@
 use dragonglass_backend_vulkan::{
     render::Renderer,
-    systems::render::{animation_system, prepare_renderer_system, reload_system, render_system},
+    systems::{
+        camera::{camera_framing_system, scene_camera_system},
+        render::{
+            animation_system, asset_loading_system, file_reload_system, prepare_renderer_system,
+            reload_system, render_system,
+        },
+    },
 };
 use dragonglass_core::{
+    animation::AnimationState,
     camera::{
-        fps_camera_key_system, fps_camera_mouse_system, orbital_camera_mouse_system, Camera,
-        CameraState,
+        fps_camera_key_system, fps_camera_mouse_system, orbital_camera_mouse_system, ActiveCamera,
+        Camera, CameraState,
     },
-    components::{AssetName, Transform},
+    components::{AssetName, Light, LightKind, Transform},
     input::Input,
-    AnimationState, AppState, DeltaTime,
+    AppState, AssetCacheStats, AssetLoadingProgress, CullingStats, DeltaTime,
 };
 use legion::prelude::*;
 use nalgebra_glm as glm;
-use std::time::Instant;
+use std::{path::PathBuf, time::Instant};
 use winit::{
     dpi::{LogicalPosition, LogicalSize},
     ElementState, Event, EventsLoop, MouseButton, MouseScrollDelta, VirtualKeyCode, Window,
@@ -26,6 +33,8 @@ struct ExitRequested(bool);
 pub struct App {
     event_loop: EventsLoop,
     window: Window,
+    title: String,
+    shown_title: String,
     should_exit: bool,
 }
 
@@ -42,6 +51,8 @@ impl App {
         App {
             event_loop,
             window,
+            title: title.to_string(),
+            shown_title: title.to_string(),
             should_exit: false,
         }
     }
@@ -87,20 +98,36 @@ impl App {
 
         world.resources.insert(CameraState::default());
 
+        world.resources.insert(ActiveCamera::default());
+
         world.resources.insert(DeltaTime(0 as _));
 
         world.resources.insert(AppState::default());
 
+        world.resources.insert(AssetLoadingProgress::default());
+        world.resources.insert(AssetCacheStats::default());
+        world.resources.insert(CullingStats::default());
+
         // Register the render preparation system and its components
         let mut prepare_schedule = Schedule::builder()
             .add_system(prepare_renderer_system())
             .build();
 
         let mut schedule = Schedule::builder()
+            // Entities are posed before their assets can be reloaded,
+            // so that their poses always belong to the asset they are drawn with
+            .add_system(animation_system())
+            // Assets are loaded and freed as asset entities come and go,
+            // and loaded again when their files change
+            .add_system(file_reload_system())
+            .add_system(asset_loading_system())
+            // The orbital camera is fitted to newly loaded assets
+            .add_system(camera_framing_system())
             .add_system(orbital_camera_mouse_system())
             // .add_system(fps_camera_mouse_system())
             // .add_system(fps_camera_key_system())
-            .add_system(animation_system())
+            // Scene cameras follow their animated nodes
+            .add_system(scene_camera_system())
             .add_system(reload_system())
             .flush()
             // More game simulation systems can go here
@@ -118,11 +145,25 @@ impl App {
                 // AssetName("examples/assets/models/Sponza/Sponza.gltf".to_string()),
                 // AssetName("examples/assets/models/BoxAnimated.glb".to_string()),
                 AssetName("examples/assets/models/DamagedHelmet.glb".to_string()),
-                AnimationState { time: 0.0 },
+                AnimationState::default(),
                 Transform::default(),
             )],
         );
 
+        let lights = [glm::vec3(1.0, 1.0, 1.0), glm::vec3(-8.0, 1.0, 0.0)]
+            .iter()
+            .map(|position| {
+                (
+                    Light::new(LightKind::Point, glm::vec3(1.0, 1.0, 1.0), 10.0),
+                    Transform {
+                        translate: glm::translation(position),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        world.insert((), lights);
+
         prepare_schedule.execute(&mut world);
 
         let mut last_frame = Instant::now();
@@ -137,6 +178,8 @@ impl App {
 
             schedule.execute(&mut world);
 
+            self.update_title(&world);
+
             let delta_time =
                 (Instant::now().duration_since(last_frame).as_millis() as f64) / 1000_f64;
             last_frame = Instant::now();
@@ -154,6 +197,41 @@ impl App {
         renderer.context.logical_device().wait_idle();
     }
 
+    // Assets load in the background, so their progress is shown in the title bar,
+    // followed by how many mesh instances survive culling once everything is loaded
+    fn update_title(&mut self, world: &World) {
+        let progress = world
+            .resources
+            .get::<AssetLoadingProgress>()
+            .expect("Failed to get asset loading progress resource!");
+        let culling_stats = world
+            .resources
+            .get::<CullingStats>()
+            .expect("Failed to get culling stats resource!");
+        let title = if progress.is_loading() {
+            format!(
+                "{} - Loading assets ({:.0}%)",
+                self.title,
+                progress.fraction() * 100.0
+            )
+        } else if culling_stats.mesh_instances > 0 {
+            format!(
+                "{} - Drawing {}/{} mesh instances ({} culled, {} draws)",
+                self.title,
+                culling_stats.visible_mesh_instances,
+                culling_stats.mesh_instances,
+                culling_stats.culled_mesh_instances(),
+                culling_stats.draws
+            )
+        } else {
+            self.title.to_string()
+        };
+        if title != self.shown_title {
+            self.window.set_title(&title);
+            self.shown_title = title;
+        }
+    }
+
     fn process_events(&mut self, world: &mut World) {
         let mut input = world
             .resources
@@ -169,6 +247,7 @@ impl App {
             .get_inner_size()
             .expect("Failed to get window inner size!");
         let mut cursor_moved = false;
+        let mut dropped_files = Vec::new();
         input.mouse.wheel_delta = 0.0;
 
         self.event_loop.poll_events(|event| {
@@ -219,6 +298,7 @@ impl App {
                     }
                     WindowEvent::DroppedFile(file_pathbuf) => {
                         println!("Received file: {:?}", file_pathbuf);
+                        dropped_files.push(file_pathbuf);
                     }
                     _ => {}
                 }
@@ -228,5 +308,38 @@ impl App {
         if !cursor_moved {
             input.mouse.position_delta = glm::vec2(0.0, 0.0);
         }
+
+        // The resources must be released before entities can be added
+        drop(input);
+        drop(app_state);
+        Self::spawn_dropped_assets(world, &dropped_files);
+    }
+
+    // Dropped gltf files are loaded by the asset loading system once their entities exist
+    fn spawn_dropped_assets(world: &mut World, dropped_files: &[PathBuf]) {
+        let assets = dropped_files
+            .iter()
+            .filter(|path| {
+                let extension = path
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .map(|extension| extension.to_lowercase());
+                let is_gltf = matches!(extension.as_deref(), Some("gltf") | Some("glb"));
+                if !is_gltf {
+                    log::warn!("Ignoring dropped file that isn't a gltf asset: {:?}", path);
+                }
+                is_gltf
+            })
+            .map(|path| {
+                (
+                    AssetName(path.to_string_lossy().to_string()),
+                    AnimationState::default(),
+                    Transform::default(),
+                )
+            })
+            .collect::<Vec<_>>();
+        if !assets.is_empty() {
+            world.insert((), assets);
+        }
     }
 }
@@ -1,29 +1,56 @@
 use crate::{
     core::VulkanContext,
     model::ModelBuffers,
+    pipelines::pbr::{ShaderInstance, ShaderMaterial, MAX_MORPH_TARGETS},
     render::Renderer,
-    resource::{ImageView, Sampler, Texture, TextureDescription},
+    resource::{
+        cache::Handle, Buffer, ImageView, Sampler, SamplerCache, Texture, TextureDescription,
+    },
+    sync::{SynchronizationSet, SynchronizationSetConstants},
 };
 use ash::vk;
-use gltf::animation::{util::ReadOutputs, Interpolation};
-use nalgebra::{Matrix4, Quaternion, UnitQuaternion};
+use dragonglass_core::{
+    animation::{AnimationBlendMode, AnimationSelection, AnimationState},
+    bounds::BoundingBox,
+    camera::Projection,
+    components::{Light, LightKind},
+};
+use gltf::{
+    accessor::{self, DataType},
+    animation::{
+        util::{ReadOutputs, Rotations},
+        Interpolation,
+    },
+    json::Value,
+    mesh::Mode,
+    texture::{MagFilter, MinFilter, WrappingMode},
+    Semantic,
+};
+use nalgebra::{DVector, Matrix4, Quaternion, UnitQuaternion};
 use nalgebra_glm as glm;
 use petgraph::{
     graph::{Graph, NodeIndex},
     prelude::*,
     visit::Dfs,
 };
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::{Add, Mul},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const GPU_INSTANCING_EXTENSION: &str = "EXT_mesh_gpu_instancing";
 
 #[derive(Debug)]
 pub enum TransformationSet {
     Translations(Vec<glm::Vec3>),
-    Rotations(Vec<glm::Vec4>),
+    Rotations(Vec<glm::Quat>),
     Scales(Vec<glm::Vec3>),
-    MorphTargetWeights(Vec<f32>),
+    MorphTargetWeights(Vec<DVector<f32>>),
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Transform {
     translation: Option<glm::Vec3>,
     rotation: Option<glm::Quat>,
@@ -31,6 +58,84 @@ pub struct Transform {
 }
 
 impl Transform {
+    // Missing components are treated as the identity transform
+    pub fn blend(&self, other: &Transform, weight: f32) -> Transform {
+        let translation = match (self.translation, other.translation) {
+            (None, None) => None,
+            (first, second) => {
+                let first = first.unwrap_or_else(glm::Vec3::zeros);
+                let second = second.unwrap_or_else(glm::Vec3::zeros);
+                Some(first + (second - first) * weight)
+            }
+        };
+
+        let rotation = match (self.rotation, other.rotation) {
+            (None, None) => None,
+            (first, second) => Some(slerp(
+                first.unwrap_or_else(glm::Quat::identity),
+                second.unwrap_or_else(glm::Quat::identity),
+                weight,
+            )),
+        };
+
+        let scale = match (self.scale, other.scale) {
+            (None, None) => None,
+            (first, second) => {
+                let first = first.unwrap_or_else(|| glm::vec3(1.0, 1.0, 1.0));
+                let second = second.unwrap_or_else(|| glm::vec3(1.0, 1.0, 1.0));
+                Some(first + (second - first) * weight)
+            }
+        };
+
+        Transform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    // Applies the difference between a pose and its reference pose on top of this transform
+    pub fn add(&self, pose: &Transform, reference: &Transform, weight: f32) -> Transform {
+        let translation = match (self.translation, pose.translation, reference.translation) {
+            (None, None, None) => None,
+            (base, pose, reference) => {
+                let base = base.unwrap_or_else(glm::Vec3::zeros);
+                let pose = pose.unwrap_or_else(glm::Vec3::zeros);
+                let reference = reference.unwrap_or_else(glm::Vec3::zeros);
+                Some(base + (pose - reference) * weight)
+            }
+        };
+
+        let rotation = match (self.rotation, pose.rotation, reference.rotation) {
+            (None, None, None) => None,
+            (base, pose, reference) => {
+                let base = base.unwrap_or_else(glm::Quat::identity);
+                let pose = pose.unwrap_or_else(glm::Quat::identity);
+                let reference = reference.unwrap_or_else(glm::Quat::identity);
+                let difference = reference.conjugate() * pose;
+                Some((base * slerp(glm::Quat::identity(), difference, weight)).normalize())
+            }
+        };
+
+        let scale = match (self.scale, pose.scale, reference.scale) {
+            (None, None, None) => None,
+            (base, pose, reference) => {
+                let one = glm::vec3(1.0, 1.0, 1.0);
+                let base = base.unwrap_or(one);
+                let pose = pose.unwrap_or(one);
+                let reference = reference.unwrap_or(one);
+                let difference = pose.component_div(&reference);
+                Some(base.component_mul(&(one + (difference - one) * weight)))
+            }
+        };
+
+        Transform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
     pub fn matrix(&self) -> glm::Mat4 {
         let mut matrix = glm::Mat4::identity();
 
@@ -50,16 +155,106 @@ impl Transform {
     }
 }
 
+// Nodes and meshes without geometry have no bounding box to merge into
+fn merge_bounding_boxes(
+    bounding_box: Option<BoundingBox>,
+    other: BoundingBox,
+) -> Option<BoundingBox> {
+    Some(bounding_box.map_or(other, |bounding_box| bounding_box.merge(&other)))
+}
+
+// Spherical interpolation along the shortest path between two rotations
+fn slerp(start: glm::Quat, end: glm::Quat, amount: f32) -> glm::Quat {
+    let end = if start.dot(&end) < 0.0 { -end } else { end };
+    let start = UnitQuaternion::from_quaternion(start);
+    let end = UnitQuaternion::from_quaternion(end);
+    start
+        .try_slerp(&end, amount, f32::EPSILON)
+        .unwrap_or_else(|| start.nlerp(&end, amount))
+        .into_inner()
+}
+
 pub type NodeGraph = Graph<Node, ()>;
 
+// Triangle list geometry that MikkTSpace tangents are generated for
+struct TangentGeometry<'a> {
+    positions: &'a [glm::Vec3],
+    normals: &'a [glm::Vec3],
+    tex_coords: &'a [glm::Vec2],
+    indices: &'a [u32],
+    tangents: Vec<glm::Vec4>,
+}
+
+impl TangentGeometry<'_> {
+    fn vertex_index(&self, face: usize, vertex: usize) -> usize {
+        self.indices[face * 3 + vertex] as usize
+    }
+}
+
+impl mikktspace::Geometry for TangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vertex: usize) -> [f32; 3] {
+        self.positions[self.vertex_index(face, vertex)].into()
+    }
+
+    fn normal(&self, face: usize, vertex: usize) -> [f32; 3] {
+        self.normals[self.vertex_index(face, vertex)].into()
+    }
+
+    fn tex_coord(&self, face: usize, vertex: usize) -> [f32; 2] {
+        self.tex_coords[self.vertex_index(face, vertex)].into()
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vertex: usize) {
+        let index = self.vertex_index(face, vertex);
+        self.tangents[index] = glm::Vec4::from(tangent);
+    }
+}
+
 pub struct Node {
-    pub animation_transform: Transform,
     pub local_transform: glm::Mat4,
     pub mesh: Option<Mesh>,
     pub skin: Option<Skin>,
     pub index: usize,
 }
 
+#[derive(Debug, Default, Clone)]
+pub struct NodePose {
+    pub transform: Transform,
+    pub weights: Option<Vec<f32>>,
+}
+
+// Animated node poses, keyed by the index of the node in the gltf document
+pub type AnimationPose = HashMap<usize, NodePose>;
+
+// Everything that follows from the transforms of an asset's nodes in one pose.
+// Transforms, weights, and node bounds are indexed by the index of the node in the gltf document.
+// Animated entities have their own pose as a component.
+#[derive(Debug, Default, Clone)]
+pub struct AssetPose {
+    pub global_transforms: Vec<glm::Mat4>,
+    // The morph target weights of each mesh
+    pub weights: Vec<Vec<f32>>,
+    // The joint matrices of every skin, starting at the asset's joint offsets
+    pub joint_matrices: Vec<glm::Mat4>,
+    // The bounds of each node's mesh in the space of the asset
+    pub node_bounding_boxes: Vec<Option<BoundingBox>>,
+    pub bounding_box: Option<BoundingBox>,
+}
+
+impl AssetPose {
+    pub fn node_transform(&self, node_index: usize) -> Option<glm::Mat4> {
+        self.global_transforms.get(node_index).copied()
+    }
+}
+
 pub struct Scene {
     pub node_graphs: Vec<NodeGraph>,
 }
@@ -67,9 +262,16 @@ pub struct Scene {
 pub struct Mesh {
     pub primitives: Vec<Primitive>,
     pub mesh_id: usize,
+    pub weights: Vec<f32>,
+    // Every entity draws one copy of the mesh per instance transform
+    pub instance_transforms: Vec<glm::Mat4>,
+    // The mesh's range of the instance buffer starts here
+    pub first_instance: usize,
+    pub bounding_box: Option<BoundingBox>,
 }
 
 pub struct Skin {
+    pub index: usize,
     pub joints: Vec<Joint>,
 }
 
@@ -79,63 +281,415 @@ pub struct Joint {
 }
 
 pub struct Primitive {
+    pub topology: vk::PrimitiveTopology,
+    pub is_indexed: bool,
     pub number_of_indices: u32,
     pub first_index: u32,
+    pub first_vertex: u32,
+    pub number_of_vertices: u32,
     pub material_index: Option<usize>,
+    // Back faces of double sided materials aren't culled
+    pub is_double_sided: bool,
+    pub morph_target_offset: u32,
+    pub number_of_morph_targets: u32,
+    pub bounding_box: Option<BoundingBox>,
 }
 
-// TODO: Properly decouple the animation state from the asset as a component to make it reusable.
 pub struct Animation {
-    pub time: f32,
+    pub name: Option<String>,
+    pub max_animation_time: f32,
     channels: Vec<Channel>,
-    max_animation_time: f32,
 }
 
 pub struct Channel {
     node_index: usize,
     inputs: Vec<f32>,
     transformations: TransformationSet,
-    _interpolation: Interpolation,
-    previous_key: usize,
-    previous_time: f32,
+    interpolation: Interpolation,
+}
+
+impl Channel {
+    // Keyframes are found by binary search, so the shared channel never changes while sampling
+    fn keyframe(&self, animation_time: f32) -> Keyframe {
+        let first_input = self.inputs[0];
+        let last_input = self.inputs[self.inputs.len() - 1];
+
+        // Channels hold their first and last values outside of their own time range
+        let time = animation_time.max(first_input).min(last_input);
+
+        if self.inputs.len() < 2 {
+            return Keyframe::default();
+        }
+
+        let next = self
+            .inputs
+            .partition_point(|input| *input < time)
+            .min(self.inputs.len() - 1)
+            .max(1);
+        let previous = next - 1;
+
+        let interval = self.inputs[next] - self.inputs[previous];
+        let amount = if interval > 0.0 {
+            (time - self.inputs[previous]) / interval
+        } else {
+            0.0
+        };
+
+        Keyframe {
+            previous,
+            next,
+            interval,
+            amount,
+        }
+    }
+}
+
+// A camera attached to a node in the gltf document
+#[derive(Debug, Clone)]
+pub struct GltfCamera {
+    pub name: Option<String>,
+    pub node_index: usize,
+    pub projection: Projection,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Keyframe {
+    previous: usize,
+    next: usize,
+    interval: f32,
+    amount: f32,
+}
+
+// Everything in an asset that can be prepared without the renderer
+pub struct GltfAssetData {
+    pub name: String,
+    pub handle: Handle<GltfAsset>,
+    // The asset file and the external buffers and images it refers to
+    pub files: Vec<PathBuf>,
+    pub gltf: gltf::Document,
+    pub images: Vec<gltf::image::Data>,
+    // Identical images have the same handle, even across assets
+    pub image_handles: Vec<Handle<GltfImageData>>,
+    pub scenes: Vec<Scene>,
+    pub vertices: Vec<f32>,
+    pub indices: Vec<u32>,
+    pub morph_targets: Vec<f32>,
+    pub animations: Vec<Animation>,
+    pub lights: Vec<(Light, usize)>,
+    pub cameras: Vec<GltfCamera>,
+}
+
+impl GltfAssetData {
+    // Parses the document and decodes its images, which is slow for large assets
+    pub fn load(asset_name: &str) -> gltf::Result<Self> {
+        let (gltf, buffers, images) = gltf::import(asset_name)?;
+        let image_handles = images
+            .iter()
+            .map(|image| {
+                Handle::from_key(&(image.width, image.height, image.format, &image.pixels))
+            })
+            .collect::<Vec<_>>();
+
+        let animations = GltfAsset::prepare_animations(&gltf, &buffers);
+        let lights = GltfAsset::prepare_lights(&gltf);
+        let cameras = GltfAsset::prepare_cameras(&gltf);
+
+        let (mut scenes, vertices, indices, morph_targets) =
+            GltfAsset::prepare_scenes(&gltf, &buffers);
+        GltfAsset::update_ubo_indices(&mut scenes);
+        GltfAsset::prepare_instance_transforms(&gltf, &buffers, &mut scenes);
+
+        Ok(Self {
+            name: asset_name.to_string(),
+            handle: GltfAsset::handle(asset_name),
+            files: GltfAsset::source_files(asset_name, &gltf),
+            gltf,
+            images,
+            image_handles,
+            scenes,
+            vertices,
+            indices,
+            morph_targets,
+            animations,
+            lights,
+            cameras,
+        })
+    }
+}
+
+// The materials of an asset, in the storage buffer read by the fragment shader
+pub struct GltfMaterialData {
+    pub buffer: Buffer,
 }
 
 pub struct GltfAsset {
+    pub name: String,
+    pub handle: Handle<GltfAsset>,
+    pub files: Vec<PathBuf>,
     pub gltf: gltf::Document,
+    pub images: Vec<Handle<GltfImageData>>,
     pub textures: Vec<GltfTextureData>,
     pub scenes: Vec<Scene>,
     pub number_of_meshes: usize,
     pub buffers: ModelBuffers,
+    pub morph_target_buffer: Buffer,
+    pub materials: Arc<GltfMaterialData>,
+    pub material_handle: Handle<GltfMaterialData>,
+    pub joint_buffer: Buffer,
+    pub joint_offsets: Vec<usize>,
+    pub number_of_joints: usize,
     pub animations: Vec<Animation>,
+    // Lights paired with the index of the node they are attached to
+    pub lights: Vec<(Light, usize)>,
+    pub cameras: Vec<GltfCamera>,
+    pub first_texture: usize,
+    // The number of entities that draw this asset
+    pub number_of_instances: usize,
+    // The number of entities the joint buffer has room for
+    pub instance_capacity: usize,
+    // The pose of entities that aren't animated
+    pub rest_pose: AssetPose,
+    // The default morph target weights of each node's mesh
+    rest_weights: HashMap<usize, Vec<f32>>,
 }
 
 impl GltfAsset {
-    pub fn new(renderer: &Renderer, asset_name: &str) -> GltfAsset {
-        let (gltf, buffers, asset_textures) =
-            gltf::import(&asset_name).expect("Couldn't import file!");
+    // Uploads an asset whose data was prepared off the render thread
+    pub fn new(renderer: &mut Renderer, data: GltfAssetData) -> GltfAsset {
+        let GltfAssetData {
+            name,
+            handle,
+            files,
+            gltf,
+            images: asset_images,
+            image_handles,
+            scenes,
+            vertices,
+            indices,
+            morph_targets,
+            animations,
+            lights,
+            cameras,
+        } = data;
 
-        let textures = asset_textures
+        // Images that are already resident are shared instead of uploaded again
+        let mut image_cache = std::mem::take(&mut renderer.image_cache);
+        let images = asset_images
             .iter()
-            .map(|properties| GltfTextureData::new(&renderer, properties))
+            .zip(image_handles.iter())
+            .map(|(properties, handle)| {
+                image_cache.get_or_insert_with(handle, || GltfImageData::new(renderer, properties))
+            })
             .collect::<Vec<_>>();
+        renderer.image_cache = image_cache;
 
-        let animations = Self::prepare_animations(&gltf, &buffers);
-
-        let (mut scenes, vertices, indices) = Self::prepare_scenes(&gltf, &buffers, &renderer);
-        Self::update_ubo_indices(&mut scenes);
+        // Materials refer to textures, which pair an image with a sampler
+        let textures = gltf
+            .textures()
+            .map(|texture| GltfTextureData::new(&mut renderer.sampler_cache, &images, &texture))
+            .collect::<Vec<_>>();
 
         let number_of_meshes = gltf.nodes().filter(|node| node.mesh().is_some()).count();
 
-        let buffers =
-            ModelBuffers::new(&renderer.transient_command_pool, &vertices, Some(&indices));
-        GltfAsset {
+        // Assets with only non-indexed primitives don't need an index buffer
+        let indices = if indices.is_empty() {
+            None
+        } else {
+            Some(indices.as_slice())
+        };
+        let buffers = ModelBuffers::new(&renderer.transient_command_pool, &vertices, indices);
+        let morph_target_buffer = Self::create_morph_target_buffer(renderer, &morph_targets);
+        let (material_handle, materials) = Self::create_materials(renderer, &gltf);
+
+        // The joint matrices of every skin are stored sequentially in the joint buffer
+        let joint_offsets = gltf
+            .skins()
+            .scan(0, |offset, skin| {
+                let joint_offset = *offset;
+                *offset += skin.joints().count();
+                Some(joint_offset)
+            })
+            .collect::<Vec<_>>();
+        let number_of_joints = gltf
+            .skins()
+            .map(|skin| skin.joints().count())
+            .sum::<usize>();
+        let joint_buffer = Self::create_joint_buffer(renderer.context.clone(), 0);
+        let rest_weights = Self::prepare_rest_weights(&scenes);
+
+        let mut asset = GltfAsset {
+            name,
+            handle,
+            files,
             gltf,
+            images: image_handles,
             textures,
             scenes,
             number_of_meshes,
             buffers,
+            morph_target_buffer,
+            materials,
+            material_handle,
+            joint_buffer,
+            joint_offsets,
+            number_of_joints,
             animations,
+            lights,
+            cameras,
+            first_texture: 0,
+            number_of_instances: 0,
+            instance_capacity: 0,
+            rest_pose: AssetPose::default(),
+            rest_weights,
+        };
+        asset.rest_pose = asset.pose(&AnimationPose::new());
+        asset
+    }
+
+    // The joint buffer holds the joint matrices of each entity one after another,
+    // with room to spare so that it only grows when it runs out of room.
+    // Every frame in flight writes its own copy of them.
+    pub fn allocate_joint_buffer(&mut self, context: Arc<VulkanContext>) {
+        self.instance_capacity = self.number_of_instances.max(1).next_power_of_two();
+        self.joint_buffer = Self::create_joint_buffer(
+            context,
+            SynchronizationSet::MAX_FRAMES_IN_FLIGHT as usize
+                * self.number_of_joints
+                * self.instance_capacity,
+        );
+    }
+
+    // Where the joint matrices of an entity start in the joint buffer in a frame
+    pub fn entity_joint_offset(&self, frame: usize, instance_index: usize) -> usize {
+        (frame * self.instance_capacity + instance_index) * self.number_of_joints
+    }
+
+    fn create_joint_buffer(context: Arc<VulkanContext>, number_of_joint_matrices: usize) -> Buffer {
+        // Storage buffers can't be empty
+        Buffer::new_mapped_basic(
+            context,
+            (number_of_joint_matrices.max(1) * std::mem::size_of::<glm::Mat4>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk_mem::MemoryUsage::CpuToGpu,
+        )
+    }
+
+    // The number of model matrices this asset needs in the instance buffer
+    pub fn number_of_instance_transforms(&self) -> usize {
+        self.meshes()
+            .map(|mesh| mesh.instance_transforms.len() * self.number_of_instances)
+            .sum()
+    }
+
+    // Gives each mesh its own range of the instance buffer and returns the end of the last range
+    pub fn assign_instance_ranges(&mut self, first_instance: usize) -> usize {
+        let number_of_instances = self.number_of_instances;
+        self.scenes
+            .iter_mut()
+            .flat_map(|scene| scene.node_graphs.iter_mut())
+            .flat_map(|graph| graph.node_weights_mut())
+            .filter_map(|node| node.mesh.as_mut())
+            .fold(first_instance, |first_instance, mesh| {
+                mesh.first_instance = first_instance;
+                first_instance + mesh.instance_transforms.len() * number_of_instances
+            })
+    }
+
+    fn meshes(&self) -> impl Iterator<Item = &Mesh> {
+        self.node_graphs()
+            .flat_map(|graph| graph.raw_nodes().iter().map(|node| &node.weight))
+            .filter_map(|node| node.mesh.as_ref())
+    }
+
+    fn node_graphs(&self) -> impl Iterator<Item = &NodeGraph> {
+        self.scenes
+            .iter()
+            .flat_map(|scene| scene.node_graphs.iter())
+    }
+
+    fn create_morph_target_buffer(renderer: &Renderer, morph_targets: &[f32]) -> Buffer {
+        // Storage buffers can't be empty, so assets without
+        // morph targets get a buffer with a single blank displacement
+        let morph_targets = if morph_targets.is_empty() {
+            &[0.0; 4]
+        } else {
+            morph_targets
+        };
+        let region = vk::BufferCopy {
+            src_offset: 0,
+            dst_offset: 0,
+            size: std::mem::size_of_val(morph_targets) as vk::DeviceSize,
+        };
+        renderer.transient_command_pool.create_device_local_buffer(
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            morph_targets,
+            &[region],
+        )
+    }
+
+    // Identical files and copies of an asset resolve to the same handle
+    pub fn handle(asset_name: &str) -> Handle<GltfAsset> {
+        let path = Path::new(asset_name);
+        Handle::from_key(&path.canonicalize().unwrap_or_else(|_| path.to_path_buf()))
+    }
+
+    pub fn source_files(asset_name: &str, gltf: &gltf::Document) -> Vec<PathBuf> {
+        let path = Path::new(asset_name);
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        // Embedded data is reloaded along with the asset file
+        let buffer_uris = gltf.buffers().filter_map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Uri(uri) => Some(uri),
+            gltf::buffer::Source::Bin => None,
+        });
+        let image_uris = gltf.images().filter_map(|image| match image.source() {
+            gltf::image::Source::Uri { uri, .. } => Some(uri),
+            gltf::image::Source::View { .. } => None,
+        });
+
+        let mut files = vec![path.to_path_buf()];
+        for uri in buffer_uris.chain(image_uris) {
+            if uri.starts_with("data:") {
+                continue;
+            }
+            let file = directory.join(uri);
+            if !files.contains(&file) {
+                files.push(file);
+            }
         }
+        files
+    }
+
+    fn create_materials(
+        renderer: &mut Renderer,
+        gltf: &gltf::Document,
+    ) -> (Handle<GltfMaterialData>, Arc<GltfMaterialData>) {
+        // A default material is appended for primitives that don't specify one
+        let materials = gltf
+            .materials()
+            .map(|material| ShaderMaterial::from_gltf(&material))
+            .chain(std::iter::once(ShaderMaterial::default()))
+            .collect::<Vec<_>>();
+        let size = std::mem::size_of_val(materials.as_slice());
+
+        // Assets with the same materials share a material buffer
+        let handle = Handle::from_key(&materials);
+
+        let command_pool = &renderer.transient_command_pool;
+        let material_data = renderer.material_cache.get_or_insert_with(&handle, || {
+            let region = vk::BufferCopy {
+                src_offset: 0,
+                dst_offset: 0,
+                size: size as vk::DeviceSize,
+            };
+            let buffer = command_pool.create_device_local_buffer(
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                &materials,
+                &[region],
+            );
+            GltfMaterialData { buffer }
+        });
+        (handle, material_data)
     }
 
     fn determine_transform(node: &gltf::Node) -> glm::Mat4 {
@@ -146,16 +700,68 @@ impl GltfAsset {
             .flat_map(|array| array.iter())
             .cloned()
             .collect();
-        glm::make_mat4(&transform.as_slice())
+        glm::make_mat4(transform.as_slice())
+    }
+
+    // Lights keep the index of their node, so they can follow the pose of the asset
+    fn prepare_lights(gltf: &gltf::Document) -> Vec<(Light, usize)> {
+        gltf.nodes()
+            .filter_map(|node| {
+                let light = node.light()?;
+                let kind = match light.kind() {
+                    gltf::khr_lights_punctual::Kind::Directional => LightKind::Directional,
+                    gltf::khr_lights_punctual::Kind::Point => LightKind::Point,
+                    gltf::khr_lights_punctual::Kind::Spot {
+                        inner_cone_angle,
+                        outer_cone_angle,
+                    } => LightKind::Spot {
+                        inner_cone_angle,
+                        outer_cone_angle,
+                    },
+                };
+                let mut asset_light =
+                    Light::new(kind, glm::Vec3::from(light.color()), light.intensity());
+                asset_light.range = light.range();
+                Some((asset_light, node.index()))
+            })
+            .collect()
+    }
+
+    fn prepare_cameras(gltf: &gltf::Document) -> Vec<GltfCamera> {
+        gltf.nodes()
+            .filter_map(|node| {
+                let camera = node.camera()?;
+                let projection = match camera.projection() {
+                    gltf::camera::Projection::Perspective(perspective) => Projection::Perspective {
+                        y_fov: perspective.yfov(),
+                        z_near: perspective.znear(),
+                        z_far: perspective.zfar(),
+                    },
+                    gltf::camera::Projection::Orthographic(orthographic) => {
+                        Projection::Orthographic {
+                            x_mag: orthographic.xmag(),
+                            y_mag: orthographic.ymag(),
+                            z_near: orthographic.znear(),
+                            z_far: orthographic.zfar(),
+                        }
+                    }
+                };
+                Some(GltfCamera {
+                    name: camera.name().map(str::to_string),
+                    node_index: node.index(),
+                    projection,
+                })
+            })
+            .collect()
     }
 
     fn prepare_scenes(
         gltf: &gltf::Document,
         buffers: &[gltf::buffer::Data],
-        renderer: &Renderer,
-    ) -> (Vec<Scene>, Vec<f32>, Vec<u32>) {
+    ) -> (Vec<Scene>, Vec<f32>, Vec<u32>, Vec<f32>) {
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
+        let mut morph_targets = Vec::new();
         let mut scenes: Vec<Scene> = Vec::new();
         for scene in gltf.scenes() {
             let mut node_graphs: Vec<NodeGraph> = Vec::new();
@@ -163,18 +769,18 @@ impl GltfAsset {
                 let mut node_graph = NodeGraph::new();
                 Self::visit_children(
                     &node,
-                    &buffers,
+                    buffers,
                     &mut node_graph,
                     NodeIndex::new(0_usize),
-                    &renderer,
                     &mut vertices,
                     &mut indices,
+                    &mut morph_targets,
                 );
                 node_graphs.push(node_graph);
             }
             scenes.push(Scene { node_graphs });
         }
-        (scenes, vertices, indices)
+        (scenes, vertices, indices, morph_targets)
     }
 
     fn load_skin(node: &gltf::Node, buffers: &[gltf::buffer::Data]) -> Option<Skin> {
@@ -199,7 +805,10 @@ impl GltfAsset {
                 });
             }
 
-            Some(Skin { joints })
+            Some(Skin {
+                index: skin.index(),
+                joints,
+            })
         } else {
             None
         }
@@ -210,14 +819,13 @@ impl GltfAsset {
         buffers: &[gltf::buffer::Data],
         node_graph: &mut NodeGraph,
         parent_index: NodeIndex,
-        renderer: &Renderer,
         vertices: &mut Vec<f32>,
         indices: &mut Vec<u32>,
+        morph_targets: &mut Vec<f32>,
     ) {
-        let mesh = Self::load_mesh(node, buffers, vertices, indices);
+        let mesh = Self::load_mesh(node, buffers, vertices, indices, morph_targets);
         let skin = Self::load_skin(node, buffers);
         let node_info = Node {
-            animation_transform: Transform::default(),
             local_transform: Self::determine_transform(node),
             mesh,
             skin,
@@ -231,7 +839,13 @@ impl GltfAsset {
 
         for child in node.children() {
             Self::visit_children(
-                &child, buffers, node_graph, node_index, renderer, vertices, indices,
+                &child,
+                buffers,
+                node_graph,
+                node_index,
+                vertices,
+                indices,
+                morph_targets,
             );
         }
     }
@@ -241,6 +855,7 @@ impl GltfAsset {
         buffers: &[gltf::buffer::Data],
         vertices: &mut Vec<f32>,
         indices: &mut Vec<u32>,
+        morph_targets: &mut Vec<f32>,
     ) -> Option<Mesh> {
         if let Some(mesh) = node.mesh() {
             let mut all_mesh_primitives = Vec::new();
@@ -251,13 +866,17 @@ impl GltfAsset {
                 let tex_coords_1_length = 2;
                 let joints_0_length = 4;
                 let weights_0_length = 4;
+                let tangent_length = 4;
+                let color_0_length = 4;
 
                 let stride = (position_length
                     + normal_length
                     + tex_coords_0_length
                     + tex_coords_1_length
                     + joints_0_length
-                    + weights_0_length)
+                    + weights_0_length
+                    + tangent_length
+                    + color_0_length)
                     * std::mem::size_of::<f32>();
 
                 let vertex_list_size = vertices.len() * std::mem::size_of::<u32>();
@@ -266,28 +885,28 @@ impl GltfAsset {
                 // Start reading primitive data
                 let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
 
-                let positions = reader
+                let mut positions = reader
                     .read_positions()
                     .expect("Failed to read any vertex positions from the model. Vertex positions are required.")
                     .map(glm::Vec3::from)
                     .collect::<Vec<_>>();
+                let number_of_source_vertices = positions.len();
+                let bounding_box = Self::primitive_bounding_box(&primitive, &positions);
 
-                let normals = reader
+                let mut normals = reader
                     .read_normals()
-                    .map_or(vec![glm::vec3(0.0, 0.0, 0.0); positions.len()], |normals| {
-                        normals.map(glm::Vec3::from).collect::<Vec<_>>()
-                    });
+                    .map(|normals| normals.map(glm::Vec3::from).collect::<Vec<_>>());
 
                 let convert_coords =
                     |coords: gltf::mesh::util::ReadTexCoords<'_>| -> Vec<glm::Vec2> {
                         coords.into_f32().map(glm::Vec2::from).collect::<Vec<_>>()
                     };
 
-                let tex_coords_0 = reader
+                let mut tex_coords_0 = reader
                     .read_tex_coords(0)
                     .map_or(vec![glm::vec2(0.0, 0.0); positions.len()], convert_coords);
 
-                let tex_coords_1 = reader
+                let mut tex_coords_1 = reader
                     .read_tex_coords(1)
                     .map_or(vec![glm::vec2(0.0, 0.0); positions.len()], convert_coords);
 
@@ -300,7 +919,7 @@ impl GltfAsset {
                         .collect::<Vec<_>>()
                 };
 
-                let joints_0 = reader.read_joints(0).map_or(
+                let mut joints_0 = reader.read_joints(0).map_or(
                     vec![glm::vec4(0.0, 0.0, 0.0, 0.0); positions.len()],
                     convert_joints,
                 );
@@ -310,11 +929,98 @@ impl GltfAsset {
                         coords.into_f32().map(glm::Vec4::from).collect::<Vec<_>>()
                     };
 
-                let weights_0 = reader.read_weights(0).map_or(
+                let mut weights_0 = reader.read_weights(0).map_or(
                     vec![glm::vec4(0.0, 0.0, 0.0, 0.0); positions.len()],
                     convert_weights,
                 );
 
+                // Vertex colors may be RGB or RGBA, normalized integers or floats
+                let mut colors_0 = reader.read_colors(0).map_or(
+                    vec![glm::vec4(1.0, 1.0, 1.0, 1.0); positions.len()],
+                    |colors| {
+                        colors
+                            .into_rgba_f32()
+                            .map(glm::Vec4::from)
+                            .collect::<Vec<_>>()
+                    },
+                );
+
+                let mut tangents = reader
+                    .read_tangents()
+                    .map(|tangents| tangents.map(glm::Vec4::from).collect::<Vec<_>>());
+
+                let mut mode = primitive.mode();
+                let mut primitive_indices = reader
+                    .read_indices()
+                    .map(|read_indices| read_indices.into_u32().collect::<Vec<_>>());
+
+                // Vulkan has no line loops, so they are drawn as line strips
+                // that return to the first vertex
+                if mode == Mode::LineLoop {
+                    let mut loop_indices = primitive_indices
+                        .take()
+                        .unwrap_or_else(|| (0..positions.len() as u32).collect::<Vec<_>>());
+                    if let Some(first_index) = loop_indices.first().copied() {
+                        loop_indices.push(first_index);
+                    }
+                    primitive_indices = Some(loop_indices);
+                    mode = Mode::LineStrip;
+                }
+
+                let is_triangles = matches!(
+                    mode,
+                    Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan
+                );
+
+                // Triangles without normals are unwelded into a non-indexed triangle list
+                // so that every face can be given its own flat normal
+                let mut unwelded_indices = None;
+                if normals.is_none() && is_triangles {
+                    let triangle_indices = Self::triangulate(
+                        mode,
+                        primitive_indices.take().as_deref(),
+                        positions.len(),
+                    );
+                    positions = Self::unweld(&positions, &triangle_indices);
+                    tex_coords_0 = Self::unweld(&tex_coords_0, &triangle_indices);
+                    tex_coords_1 = Self::unweld(&tex_coords_1, &triangle_indices);
+                    joints_0 = Self::unweld(&joints_0, &triangle_indices);
+                    weights_0 = Self::unweld(&weights_0, &triangle_indices);
+                    colors_0 = Self::unweld(&colors_0, &triangle_indices);
+                    tangents = tangents.map(|tangents| Self::unweld(&tangents, &triangle_indices));
+                    normals = Some(Self::calculate_flat_normals(&positions));
+                    mode = Mode::Triangles;
+                    unwelded_indices = Some(triangle_indices);
+                }
+
+                let has_normals = normals.is_some();
+                let normals =
+                    normals.unwrap_or_else(|| vec![glm::vec3(0.0, 0.0, 0.0); positions.len()]);
+
+                // Tangents can only be generated if there are normals and texture coordinates.
+                // They follow the texture coordinates that the normal texture is sampled with.
+                let normal_tex_coord_set = primitive
+                    .material()
+                    .normal_texture()
+                    .map_or(0, |normal_texture| normal_texture.tex_coord());
+                let tangents = match tangents {
+                    Some(tangents) => tangents,
+                    None if is_triangles
+                        && has_normals
+                        && reader.read_tex_coords(normal_tex_coord_set).is_some() =>
+                    {
+                        let triangle_indices =
+                            Self::triangulate(mode, primitive_indices.as_deref(), positions.len());
+                        let tex_coords = if normal_tex_coord_set == 1 {
+                            &tex_coords_1
+                        } else {
+                            &tex_coords_0
+                        };
+                        Self::generate_tangents(&positions, &normals, tex_coords, &triangle_indices)
+                    }
+                    None => vec![glm::vec4(0.0, 0.0, 0.0, 0.0); positions.len()],
+                };
+
                 for index in 0..positions.len() {
                     vertices.extend_from_slice(positions[index].as_slice());
                     vertices.extend_from_slice(normals[index].as_slice());
@@ -322,40 +1028,372 @@ impl GltfAsset {
                     vertices.extend_from_slice(tex_coords_1[index].as_slice());
                     vertices.extend_from_slice(joints_0[index].as_slice());
                     vertices.extend_from_slice(weights_0[index].as_slice());
+                    vertices.extend_from_slice(tangents[index].as_slice());
+                    vertices.extend_from_slice(colors_0[index].as_slice());
                 }
 
-                let first_index = indices.len() as u32;
-
-                let primitive_indices = reader
-                    .read_indices()
-                    .map(|read_indices| {
-                        read_indices
-                            .into_u32()
-                            .map(|x| x + vertex_count)
-                            .collect::<Vec<_>>()
-                    })
-                    .expect("Failed to read indices!");
-                indices.extend_from_slice(&primitive_indices);
+                let morph_target_offset = (morph_targets.len() / 4) as u32;
+                let number_of_morph_targets = primitive.morph_targets().count();
+                if number_of_morph_targets > MAX_MORPH_TARGETS {
+                    log::warn!(
+                        "Mesh has {} morph targets but only {} can be weighted at once",
+                        number_of_morph_targets,
+                        MAX_MORPH_TARGETS
+                    );
+                }
+                Self::load_morph_targets(
+                    &reader,
+                    number_of_source_vertices,
+                    unwelded_indices.as_deref(),
+                    morph_targets,
+                );
 
-                let number_of_indices = primitive_indices.len() as u32;
+                let first_index = indices.len() as u32;
+                let number_of_indices = primitive_indices
+                    .as_ref()
+                    .map_or(0, |primitive_indices| primitive_indices.len() as u32);
+                if let Some(primitive_indices) = primitive_indices.as_ref() {
+                    indices.extend(primitive_indices.iter().map(|index| index + vertex_count));
+                }
 
                 all_mesh_primitives.push(Primitive {
+                    topology: Self::topology(mode),
+                    is_indexed: primitive_indices.is_some(),
                     first_index,
                     number_of_indices,
+                    first_vertex: vertex_count,
+                    number_of_vertices: positions.len() as u32,
                     material_index: primitive.material().index(),
+                    is_double_sided: primitive.material().double_sided(),
+                    morph_target_offset,
+                    number_of_morph_targets: number_of_morph_targets as u32,
+                    bounding_box,
                 });
             }
 
+            let bounding_box = all_mesh_primitives
+                .iter()
+                .filter_map(|primitive| primitive.bounding_box)
+                .fold(None, merge_bounding_boxes);
+
+            // Node weights take precedence over the default mesh weights
+            let weights = node
+                .weights()
+                .or_else(|| mesh.weights())
+                .map_or(Vec::new(), |weights| weights.to_vec());
+
             Some(Mesh {
                 primitives: all_mesh_primitives,
                 mesh_id: 0,
+                weights,
+                instance_transforms: vec![glm::Mat4::identity()],
+                first_instance: 0,
+                bounding_box,
             })
         } else {
             None
         }
     }
 
-    fn update_ubo_indices(scenes: &mut Vec<Scene>) {
+    fn primitive_bounding_box(
+        primitive: &gltf::Primitive,
+        positions: &[glm::Vec3],
+    ) -> Option<BoundingBox> {
+        // Position accessors are required to have bounds, but not every exporter writes them
+        let bounding_box = primitive
+            .get(&Semantic::Positions)
+            .and_then(|accessor| Self::accessor_bounding_box(&accessor))
+            .or_else(|| BoundingBox::from_points(positions.iter().copied()))?;
+
+        // Morph targets can move vertices outside of the base positions,
+        // assuming that their weights are between zero and one
+        let zero = glm::Vec3::zeros();
+        let bounding_box = primitive
+            .morph_targets()
+            .filter_map(|target| target.positions())
+            .filter_map(|accessor| Self::accessor_bounding_box(&accessor))
+            .fold(bounding_box, |bounding_box, displacement| {
+                BoundingBox::new(
+                    bounding_box.min + glm::min2(&displacement.min, &zero),
+                    bounding_box.max + glm::max2(&displacement.max, &zero),
+                )
+            });
+        Some(bounding_box)
+    }
+
+    fn accessor_bounding_box(accessor: &gltf::Accessor) -> Option<BoundingBox> {
+        // The bounds of quantized positions are in their integer range
+        if accessor.data_type() != DataType::F32 {
+            return None;
+        }
+
+        let read_vector = |value: Value| -> Option<glm::Vec3> {
+            match value.as_array()?.as_slice() {
+                [x, y, z] => Some(glm::vec3(
+                    x.as_f64()? as f32,
+                    y.as_f64()? as f32,
+                    z.as_f64()? as f32,
+                )),
+                _ => None,
+            }
+        };
+        Some(BoundingBox::new(
+            read_vector(accessor.min()?)?,
+            read_vector(accessor.max()?)?,
+        ))
+    }
+
+    // Calculates the global transforms of a pose top-down in a single pass,
+    // and the joint matrices and bounds from them
+    pub fn pose(&self, animation_pose: &AnimationPose) -> AssetPose {
+        let number_of_nodes = self.gltf.nodes().len();
+        let mut global_transforms = vec![glm::Mat4::identity(); number_of_nodes];
+        let mut weights = vec![Vec::new(); number_of_nodes];
+        for graph in self.node_graphs() {
+            let mut stack = vec![(NodeIndex::new(0), glm::Mat4::identity())];
+            while let Some((node_index, parent_transform)) = stack.pop() {
+                let node = &graph[node_index];
+                let node_pose = animation_pose.get(&node.index);
+                let animation_transform = node_pose.map_or_else(glm::Mat4::identity, |node_pose| {
+                    node_pose.transform.matrix()
+                });
+                let transform = parent_transform * node.local_transform * animation_transform;
+                global_transforms[node.index] = transform;
+
+                // Animated weights replace the default mesh weights
+                if let Some(mesh) = node.mesh.as_ref() {
+                    weights[node.index] = node_pose
+                        .and_then(|node_pose| node_pose.weights.clone())
+                        .unwrap_or_else(|| mesh.weights.clone());
+                }
+
+                for child_index in graph.neighbors_directed(node_index, Outgoing) {
+                    stack.push((child_index, transform));
+                }
+            }
+        }
+
+        let mut joint_matrices = vec![glm::Mat4::identity(); self.number_of_joints];
+        for skin in self.node_graphs().flat_map(|graph| {
+            graph
+                .raw_nodes()
+                .iter()
+                .filter_map(|node| node.weight.skin.as_ref())
+        }) {
+            let joint_offset = self.joint_offsets[skin.index];
+            for (joint_index, joint) in skin.joints.iter().enumerate() {
+                joint_matrices[joint_offset + joint_index] =
+                    global_transforms[joint.index] * joint.inverse_bind_matrix;
+            }
+        }
+
+        let mut node_bounding_boxes = vec![None; number_of_nodes];
+        let mut asset_bounding_box = None;
+        for node in self
+            .node_graphs()
+            .flat_map(|graph| graph.raw_nodes().iter().map(|node| &node.weight))
+        {
+            let bounding_box = Self::node_bounding_box(
+                node,
+                &global_transforms,
+                &joint_matrices,
+                &self.joint_offsets,
+            );
+            node_bounding_boxes[node.index] = bounding_box;
+            if let Some(bounding_box) = bounding_box {
+                asset_bounding_box = merge_bounding_boxes(asset_bounding_box, bounding_box);
+            }
+        }
+
+        AssetPose {
+            global_transforms,
+            weights,
+            joint_matrices,
+            node_bounding_boxes,
+            bounding_box: asset_bounding_box,
+        }
+    }
+
+    fn node_bounding_box(
+        node: &Node,
+        global_transforms: &[glm::Mat4],
+        joint_matrices: &[glm::Mat4],
+        joint_offsets: &[usize],
+    ) -> Option<BoundingBox> {
+        let mesh = node.mesh.as_ref()?;
+        let mesh_bounding_box = mesh.bounding_box?;
+
+        // Skinned vertices are moved by their joints instead of the node transform,
+        // and end up within the bounds moved by any of the joints
+        let transforms = match node.skin.as_ref() {
+            Some(skin) => {
+                let joint_offset = joint_offsets[skin.index];
+                joint_matrices[joint_offset..joint_offset + skin.joints.len()]
+                    .iter()
+                    .flat_map(|joint_matrix| {
+                        mesh.instance_transforms
+                            .iter()
+                            .map(move |instance_transform| instance_transform * joint_matrix)
+                    })
+                    .collect::<Vec<_>>()
+            }
+            None => mesh
+                .instance_transforms
+                .iter()
+                .map(|instance_transform| global_transforms[node.index] * instance_transform)
+                .collect::<Vec<_>>(),
+        };
+
+        transforms
+            .iter()
+            .map(|transform| mesh_bounding_box.transform(transform))
+            .fold(None, merge_bounding_boxes)
+    }
+
+    fn generate_tangents(
+        positions: &[glm::Vec3],
+        normals: &[glm::Vec3],
+        tex_coords: &[glm::Vec2],
+        indices: &[u32],
+    ) -> Vec<glm::Vec4> {
+        let mut geometry = TangentGeometry {
+            positions,
+            normals,
+            tex_coords,
+            indices,
+            tangents: vec![glm::vec4(0.0, 0.0, 0.0, 0.0); positions.len()],
+        };
+        if !mikktspace::generate_tangents(&mut geometry) {
+            log::warn!("Failed to generate tangents for a primitive!");
+        }
+        geometry.tangents
+    }
+
+    fn topology(mode: Mode) -> vk::PrimitiveTopology {
+        match mode {
+            Mode::Points => vk::PrimitiveTopology::POINT_LIST,
+            Mode::Lines => vk::PrimitiveTopology::LINE_LIST,
+            Mode::LineLoop | Mode::LineStrip => vk::PrimitiveTopology::LINE_STRIP,
+            Mode::Triangles => vk::PrimitiveTopology::TRIANGLE_LIST,
+            Mode::TriangleStrip => vk::PrimitiveTopology::TRIANGLE_STRIP,
+            Mode::TriangleFan => vk::PrimitiveTopology::TRIANGLE_FAN,
+        }
+    }
+
+    // Converts triangle strips and fans into the indices of a triangle list
+    fn triangulate(mode: Mode, indices: Option<&[u32]>, number_of_vertices: usize) -> Vec<u32> {
+        let indices = indices.map_or_else(
+            || (0..number_of_vertices as u32).collect::<Vec<_>>(),
+            |indices| indices.to_vec(),
+        );
+
+        let mut triangle_indices = Vec::new();
+        match mode {
+            Mode::TriangleStrip => {
+                for index in 0..indices.len().saturating_sub(2) {
+                    // Every other triangle in a strip is flipped to preserve the winding order
+                    if index % 2 == 0 {
+                        triangle_indices.extend_from_slice(&[
+                            indices[index],
+                            indices[index + 1],
+                            indices[index + 2],
+                        ]);
+                    } else {
+                        triangle_indices.extend_from_slice(&[
+                            indices[index + 1],
+                            indices[index],
+                            indices[index + 2],
+                        ]);
+                    }
+                }
+            }
+            Mode::TriangleFan => {
+                for index in 1..indices.len().saturating_sub(1) {
+                    triangle_indices.extend_from_slice(&[
+                        indices[index],
+                        indices[index + 1],
+                        indices[0],
+                    ]);
+                }
+            }
+            _ => {
+                triangle_indices = indices;
+                triangle_indices.truncate(triangle_indices.len() - triangle_indices.len() % 3);
+            }
+        }
+        triangle_indices
+    }
+
+    fn unweld<T: Copy>(values: &[T], indices: &[u32]) -> Vec<T> {
+        indices
+            .iter()
+            .map(|index| values[*index as usize])
+            .collect::<Vec<_>>()
+    }
+
+    fn calculate_flat_normals(positions: &[glm::Vec3]) -> Vec<glm::Vec3> {
+        positions
+            .chunks(3)
+            .flat_map(|triangle| {
+                let normal = (triangle[1] - triangle[0]).cross(&(triangle[2] - triangle[0]));
+                let normal = if normal.norm() > 0.0 {
+                    normal.normalize()
+                } else {
+                    normal
+                };
+                vec![normal; triangle.len()]
+            })
+            .collect::<Vec<_>>()
+    }
+
+    fn load_morph_targets<'a, 's, F>(
+        reader: &gltf::mesh::Reader<'a, 's, F>,
+        number_of_vertices: usize,
+        unwelded_indices: Option<&[u32]>,
+        morph_targets: &mut Vec<f32>,
+    ) where
+        F: Clone + Fn(gltf::Buffer<'a>) -> Option<&'s [u8]>,
+    {
+        let read_displacements = |displacements: Option<gltf::accessor::Iter<'s, [f32; 3]>>| {
+            displacements.map_or(
+                vec![glm::vec3(0.0, 0.0, 0.0); number_of_vertices],
+                |displacements| displacements.map(glm::Vec3::from).collect::<Vec<_>>(),
+            )
+        };
+
+        let targets = reader
+            .read_morph_targets()
+            .map(|(positions, normals, tangents)| {
+                (
+                    read_displacements(positions),
+                    read_displacements(normals),
+                    read_displacements(tangents),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Displacements are interleaved per vertex so that the vertex shader
+        // can find all of a vertex's targets from a single offset
+        let vertex_indices = unwelded_indices.map_or_else(
+            || (0..number_of_vertices).collect::<Vec<_>>(),
+            |indices| {
+                indices
+                    .iter()
+                    .map(|index| *index as usize)
+                    .collect::<Vec<_>>()
+            },
+        );
+        for index in vertex_indices {
+            for (positions, normals, tangents) in targets.iter() {
+                for displacement in [positions[index], normals[index], tangents[index]].iter() {
+                    morph_targets.extend_from_slice(displacement.as_slice());
+                    morph_targets.push(0.0);
+                }
+            }
+        }
+    }
+
+    fn update_ubo_indices(scenes: &mut [Scene]) {
         let mut indices = Vec::new();
         for (scene_index, scene) in scenes.iter().enumerate() {
             for (graph_index, graph) in scene.node_graphs.iter().enumerate() {
@@ -377,58 +1415,159 @@ impl GltfAsset {
         }
     }
 
-    // TODO: Write this method for vec3's and vec4's
-    // fn interpolate(interpolation: Interpolation) {
-    //     match interpolation {
-    //         Interpolation::Linear => {}
-    //         Interpolation::Step => {}
-    //         Interpolation::CatmullRomSpline => {}
-    //         Interpolation::CubicSpline => {}
-    //     }
-    // }
+    fn prepare_instance_transforms(
+        gltf: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        scenes: &mut [Scene],
+    ) {
+        let instance_transforms = gltf
+            .nodes()
+            .filter_map(|node| {
+                Self::load_instance_transforms(gltf, &node, buffers)
+                    .map(|transforms| (node.index(), transforms))
+            })
+            .collect::<HashMap<_, _>>();
+
+        for graph in scenes
+            .iter_mut()
+            .flat_map(|scene| scene.node_graphs.iter_mut())
+        {
+            for node in graph.node_weights_mut() {
+                if let (Some(mesh), Some(transforms)) =
+                    (node.mesh.as_mut(), instance_transforms.get(&node.index))
+                {
+                    mesh.instance_transforms = transforms.clone();
+                }
+            }
+        }
+    }
+
+    fn load_instance_transforms(
+        gltf: &gltf::Document,
+        node: &gltf::Node,
+        buffers: &[gltf::buffer::Data],
+    ) -> Option<Vec<glm::Mat4>> {
+        let attributes = node
+            .extension_value(GPU_INSTANCING_EXTENSION)?
+            .get("attributes")?;
+        let attribute = |name: &str| {
+            attributes
+                .get(name)
+                .and_then(Value::as_u64)
+                .and_then(|index| gltf.accessors().nth(index as usize))
+        };
+        let get_buffer_data = |buffer: gltf::Buffer| Some(buffers[buffer.index()].0.as_slice());
+
+        let translations = attribute("TRANSLATION")
+            .and_then(|accessor| accessor::Iter::<[f32; 3]>::new(accessor, get_buffer_data))
+            .map(|translations| translations.map(glm::Vec3::from).collect::<Vec<_>>());
+        let scales = attribute("SCALE")
+            .and_then(|accessor| accessor::Iter::<[f32; 3]>::new(accessor, get_buffer_data))
+            .map(|scales| scales.map(glm::Vec3::from).collect::<Vec<_>>());
+
+        // Rotations may also be stored as normalized integers
+        let rotations = attribute("ROTATION").and_then(|accessor| {
+            let rotations = match accessor.data_type() {
+                DataType::I8 => Rotations::I8(accessor::Iter::new(accessor, get_buffer_data)?),
+                DataType::U8 => Rotations::U8(accessor::Iter::new(accessor, get_buffer_data)?),
+                DataType::I16 => Rotations::I16(accessor::Iter::new(accessor, get_buffer_data)?),
+                DataType::U16 => Rotations::U16(accessor::Iter::new(accessor, get_buffer_data)?),
+                DataType::F32 => Rotations::F32(accessor::Iter::new(accessor, get_buffer_data)?),
+                DataType::U32 => return None,
+            };
+            Some(
+                rotations
+                    .into_f32()
+                    .map(|rotation| {
+                        Quaternion::new(rotation[3], rotation[0], rotation[1], rotation[2])
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        });
+
+        let number_of_instances = [
+            translations.as_ref().map(Vec::len),
+            rotations.as_ref().map(Vec::len),
+            scales.as_ref().map(Vec::len),
+        ]
+        .iter()
+        .filter_map(|count| *count)
+        .max()?;
+        if number_of_instances == 0 {
+            log::warn!("Skipping gpu instancing for a node without instances!");
+            return None;
+        }
+
+        let transforms = (0..number_of_instances)
+            .map(|instance| {
+                Transform {
+                    translation: translations
+                        .as_ref()
+                        .and_then(|translations| translations.get(instance).copied()),
+                    rotation: rotations
+                        .as_ref()
+                        .and_then(|rotations| rotations.get(instance).copied()),
+                    scale: scales
+                        .as_ref()
+                        .and_then(|scales| scales.get(instance).copied()),
+                }
+                .matrix()
+            })
+            .collect::<Vec<_>>();
+        Some(transforms)
+    }
 
     fn prepare_animations(gltf: &gltf::Document, buffers: &[gltf::buffer::Data]) -> Vec<Animation> {
-        // TODO: load names if present as well
         let mut animations = Vec::new();
         for animation in gltf.animations() {
             let mut channels = Vec::new();
             for channel in animation.channels() {
                 let sampler = channel.sampler();
-                let _interpolation = sampler.interpolation();
+                let interpolation = sampler.interpolation();
                 let node_index = channel.target().node().index();
                 let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
                 let inputs = reader.read_inputs().unwrap().collect::<Vec<_>>();
                 let outputs = reader.read_outputs().unwrap();
-                let transformations: TransformationSet;
-                match outputs {
+                let transformations = match outputs {
                     ReadOutputs::Translations(translations) => {
                         let translations = translations.map(glm::Vec3::from).collect::<Vec<_>>();
-                        transformations = TransformationSet::Translations(translations);
+                        TransformationSet::Translations(translations)
                     }
                     ReadOutputs::Rotations(rotations) => {
                         let rotations = rotations
                             .into_f32()
-                            .map(glm::Vec4::from)
+                            .map(|rotation| {
+                                Quaternion::new(rotation[3], rotation[0], rotation[1], rotation[2])
+                            })
                             .collect::<Vec<_>>();
-                        transformations = TransformationSet::Rotations(rotations);
+                        TransformationSet::Rotations(rotations)
                     }
                     ReadOutputs::Scales(scales) => {
                         let scales = scales.map(glm::Vec3::from).collect::<Vec<_>>();
-                        transformations = TransformationSet::Scales(scales);
+                        TransformationSet::Scales(scales)
                     }
                     ReadOutputs::MorphTargetWeights(weights) => {
-                        let morph_target_weights = weights.into_f32().collect::<Vec<_>>();
-                        transformations =
-                            TransformationSet::MorphTargetWeights(morph_target_weights);
+                        let weights = weights.into_f32().collect::<Vec<_>>();
+
+                        // Weights are output as one flat list,
+                        // so they are split into one set of weights per output
+                        let mut number_of_outputs = inputs.len();
+                        if interpolation == Interpolation::CubicSpline {
+                            number_of_outputs *= 3;
+                        }
+                        let number_of_targets = weights.len() / number_of_outputs.max(1);
+                        let morph_target_weights = weights
+                            .chunks(number_of_targets.max(1))
+                            .map(DVector::from_column_slice)
+                            .collect::<Vec<_>>();
+                        TransformationSet::MorphTargetWeights(morph_target_weights)
                     }
-                }
+                };
                 channels.push(Channel {
                     node_index,
                     inputs,
                     transformations,
-                    _interpolation,
-                    previous_key: 0,
-                    previous_time: 0.0,
+                    interpolation,
                 });
             }
 
@@ -438,99 +1577,279 @@ impl GltfAsset {
                 .fold(0.0, f32::max);
 
             animations.push(Animation {
-                channels,
-                time: 0.0,
+                name: animation.name().map(str::to_string),
                 max_animation_time,
+                channels,
             });
         }
         animations
     }
 
-    pub fn animate(&mut self) {
-        // TODO: Allow for specifying a specific animation by name
-        for animation in self.animations.iter_mut() {
-            if animation.time > animation.max_animation_time {
-                animation.time = 0.0;
-            }
-            if animation.time < 0.0 {
-                animation.time = animation.max_animation_time;
-            }
-            for channel in animation.channels.iter_mut() {
-                for scene in self.scenes.iter_mut() {
-                    for graph in scene.node_graphs.iter_mut() {
-                        for node_index in graph.node_indices() {
-                            if graph[node_index].index == channel.node_index {
-                                let max = *channel.inputs.last().unwrap();
-                                let mut time = animation.time % max;
-                                let first_input = channel.inputs.first().unwrap();
-                                if time.lt(first_input) {
-                                    time = *first_input;
-                                }
-
-                                if channel.previous_time > time {
-                                    channel.previous_key = 0;
-                                }
-                                channel.previous_time = time;
-
-                                let mut next_key: usize = 0;
-                                for index in channel.previous_key..channel.inputs.len() {
-                                    let index = index as usize;
-                                    if time <= channel.inputs[index] {
-                                        next_key =
-                                            nalgebra::clamp(index, 1, channel.inputs.len() - 1);
-                                        break;
-                                    }
-                                }
-                                channel.previous_key = nalgebra::clamp(next_key - 1, 0, next_key);
-
-                                let key_delta =
-                                    channel.inputs[next_key] - channel.inputs[channel.previous_key];
-                                let normalized_time =
-                                    (time - channel.inputs[channel.previous_key]) / key_delta;
-
-                                // TODO: Interpolate with other methods
-                                // Only Linear interpolation is used for now
-                                match &channel.transformations {
-                                    TransformationSet::Translations(translations) => {
-                                        let start = translations[channel.previous_key];
-                                        let end = translations[next_key];
-                                        let translation = start.lerp(&end, normalized_time);
-                                        let translation_vec =
-                                            glm::make_vec3(translation.as_slice());
-                                        graph[node_index].animation_transform.translation =
-                                            Some(translation_vec);
-                                    }
-                                    TransformationSet::Rotations(rotations) => {
-                                        let start = rotations[channel.previous_key];
-                                        let end = rotations[next_key];
-                                        let start_quat =
-                                            Quaternion::new(start[3], start[0], start[1], start[2]);
-                                        let end_quat =
-                                            Quaternion::new(end[3], end[0], end[1], end[2]);
-                                        let rotation_quat =
-                                            start_quat.lerp(&end_quat, normalized_time);
-                                        graph[node_index].animation_transform.rotation =
-                                            Some(rotation_quat);
-                                    }
-                                    TransformationSet::Scales(scales) => {
-                                        let start = scales[channel.previous_key];
-                                        let end = scales[next_key];
-                                        let scale = start.lerp(&end, normalized_time);
-                                        let scale_vec = glm::make_vec3(scale.as_slice());
-                                        graph[node_index].animation_transform.scale =
-                                            Some(scale_vec);
-                                    }
-                                    TransformationSet::MorphTargetWeights(_weights) => {
-                                        unimplemented!()
-                                    }
-                                }
-
-                                break;
-                            }
-                        }
+    pub fn animation_index(&self, selection: &AnimationSelection) -> Option<usize> {
+        match selection {
+            AnimationSelection::Index(index) if *index < self.animations.len() => Some(*index),
+            AnimationSelection::Index(_) => None,
+            AnimationSelection::Name(name) => self
+                .animations
+                .iter()
+                .position(|animation| animation.name.as_ref() == Some(name)),
+        }
+    }
+
+    pub fn advance_animation(&self, state: &mut AnimationState, delta_time: f32) {
+        let duration = self
+            .animation_index(&state.animation)
+            .map_or(0.0, |index| self.animations[index].max_animation_time);
+        state.advance(delta_time, duration);
+
+        if let Some(crossfade) = state.crossfade.as_mut() {
+            self.advance_animation(&mut crossfade.from, delta_time);
+        }
+
+        for layer in state.layers.iter_mut() {
+            self.advance_animation(&mut layer.state, delta_time);
+        }
+    }
+
+    // Assets without animations always stay in their rest pose
+    pub fn animate(&self, state: &AnimationState) -> Option<AssetPose> {
+        if self.animations.is_empty() {
+            return None;
+        }
+        let animation_pose = self.evaluate_animation(state);
+        Some(self.pose(&animation_pose))
+    }
+
+    pub fn evaluate_animation(&self, state: &AnimationState) -> AnimationPose {
+        let mut pose = self
+            .animation_index(&state.animation)
+            .map_or(AnimationPose::new(), |index| {
+                self.sample_animation(index, state.time)
+            });
+
+        if let Some(crossfade) = state.crossfade.as_ref() {
+            let previous_pose = self.evaluate_animation(&crossfade.from);
+            pose = self.blend_poses(&previous_pose, &pose, crossfade.weight());
+        }
+
+        for layer in state.layers.iter() {
+            let layer_pose = self.evaluate_animation(&layer.state);
+            pose = match layer.blend_mode {
+                AnimationBlendMode::Override => self.blend_poses(&pose, &layer_pose, layer.weight),
+                AnimationBlendMode::Additive => {
+                    let reference_pose = self
+                        .animation_index(&layer.state.animation)
+                        .map_or(AnimationPose::new(), |index| {
+                            self.sample_animation(index, 0.0)
+                        });
+                    self.add_poses(&pose, &layer_pose, &reference_pose, layer.weight)
+                }
+            };
+        }
+
+        pose
+    }
+
+    pub fn sample_animation(&self, animation_index: usize, time: f32) -> AnimationPose {
+        let mut pose = AnimationPose::new();
+        if let Some(animation) = self.animations.get(animation_index) {
+            for channel in animation.channels.iter() {
+                let keyframe = channel.keyframe(time);
+                let interpolation = channel.interpolation;
+                let node_pose = pose.entry(channel.node_index).or_default();
+                match &channel.transformations {
+                    TransformationSet::Translations(translations) => {
+                        node_pose.transform.translation =
+                            Some(Self::interpolate(translations, interpolation, &keyframe));
+                    }
+                    TransformationSet::Rotations(rotations) => {
+                        node_pose.transform.rotation = Some(Self::interpolate_rotation(
+                            rotations,
+                            interpolation,
+                            &keyframe,
+                        ));
+                    }
+                    TransformationSet::Scales(scales) => {
+                        node_pose.transform.scale =
+                            Some(Self::interpolate(scales, interpolation, &keyframe));
                     }
+                    TransformationSet::MorphTargetWeights(weights) => {
+                        let weights = Self::interpolate(weights, interpolation, &keyframe);
+                        node_pose.weights = Some(weights.as_slice().to_vec());
+                    }
+                }
+            }
+        }
+        pose
+    }
+
+    pub fn blend_poses(
+        &self,
+        first: &AnimationPose,
+        second: &AnimationPose,
+        weight: f32,
+    ) -> AnimationPose {
+        let node_indices = first.keys().chain(second.keys()).collect::<HashSet<_>>();
+        node_indices
+            .into_iter()
+            .map(|node_index| {
+                let default_pose = NodePose::default();
+                let first = first.get(node_index).unwrap_or(&default_pose);
+                let second = second.get(node_index).unwrap_or(&default_pose);
+
+                let weights = if first.weights.is_none() && second.weights.is_none() {
+                    None
+                } else {
+                    let rest_weights = self.rest_weights(*node_index);
+                    let first_weights = first.weights.as_deref().unwrap_or(rest_weights);
+                    let second_weights = second.weights.as_deref().unwrap_or(rest_weights);
+                    Some(
+                        (0..first_weights.len().max(second_weights.len()))
+                            .map(|index| {
+                                let first = Self::weight_at(first_weights, rest_weights, index);
+                                let second = Self::weight_at(second_weights, rest_weights, index);
+                                first + (second - first) * weight
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                };
+
+                let pose = NodePose {
+                    transform: first.transform.blend(&second.transform, weight),
+                    weights,
+                };
+                (*node_index, pose)
+            })
+            .collect()
+    }
+
+    pub fn add_poses(
+        &self,
+        base: &AnimationPose,
+        pose: &AnimationPose,
+        reference: &AnimationPose,
+        weight: f32,
+    ) -> AnimationPose {
+        let node_indices = base.keys().chain(pose.keys()).collect::<HashSet<_>>();
+        node_indices
+            .into_iter()
+            .map(|node_index| {
+                let default_pose = NodePose::default();
+                let base = base.get(node_index).unwrap_or(&default_pose);
+                let pose = pose.get(node_index).unwrap_or(&default_pose);
+                let reference = reference.get(node_index).unwrap_or(&default_pose);
+
+                let weights = if base.weights.is_none() && pose.weights.is_none() {
+                    None
+                } else {
+                    let rest_weights = self.rest_weights(*node_index);
+                    let base_weights = base.weights.as_deref().unwrap_or(rest_weights);
+                    let pose_weights = pose.weights.as_deref().unwrap_or(rest_weights);
+                    let reference_weights = reference.weights.as_deref().unwrap_or(rest_weights);
+                    Some(
+                        (0..base_weights.len().max(pose_weights.len()))
+                            .map(|index| {
+                                Self::weight_at(base_weights, rest_weights, index)
+                                    + (Self::weight_at(pose_weights, rest_weights, index)
+                                        - Self::weight_at(reference_weights, rest_weights, index))
+                                        * weight
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                };
+
+                let pose = NodePose {
+                    transform: base
+                        .transform
+                        .add(&pose.transform, &reference.transform, weight),
+                    weights,
+                };
+                (*node_index, pose)
+            })
+            .collect()
+    }
+
+    fn weight_at(weights: &[f32], rest_weights: &[f32], index: usize) -> f32 {
+        weights
+            .get(index)
+            .or_else(|| rest_weights.get(index))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    // The default morph target weights of a node's mesh
+    fn rest_weights(&self, node_index: usize) -> &[f32] {
+        self.rest_weights
+            .get(&node_index)
+            .map_or(&[], |weights| weights.as_slice())
+    }
+
+    fn prepare_rest_weights(scenes: &[Scene]) -> HashMap<usize, Vec<f32>> {
+        let mut rest_weights = HashMap::new();
+        for graph in scenes.iter().flat_map(|scene| scene.node_graphs.iter()) {
+            for node in graph.node_indices().map(|index| &graph[index]) {
+                if let Some(mesh) = node.mesh.as_ref() {
+                    rest_weights
+                        .entry(node.index)
+                        .or_insert_with(|| mesh.weights.clone());
+                }
+            }
+        }
+        rest_weights
+    }
+
+    fn interpolate<T>(values: &[T], interpolation: Interpolation, keyframe: &Keyframe) -> T
+    where
+        T: Clone + Add<Output = T> + Mul<f32, Output = T>,
+    {
+        let Keyframe {
+            previous,
+            next,
+            interval,
+            amount,
+        } = *keyframe;
+
+        match interpolation {
+            Interpolation::Step => {
+                if amount < 1.0 {
+                    values[previous].clone()
+                } else {
+                    values[next].clone()
                 }
             }
+            Interpolation::CubicSpline => {
+                // Cubic spline outputs are stored as (in-tangent, value, out-tangent) triplets
+                let start = values[previous * 3 + 1].clone();
+                let start_out_tangent = values[previous * 3 + 2].clone();
+                let end_in_tangent = values[next * 3].clone();
+                let end = values[next * 3 + 1].clone();
+
+                let squared = amount * amount;
+                let cubed = squared * amount;
+
+                start * (2.0 * cubed - 3.0 * squared + 1.0)
+                    + start_out_tangent * ((cubed - 2.0 * squared + amount) * interval)
+                    + end * (-2.0 * cubed + 3.0 * squared)
+                    + end_in_tangent * ((cubed - squared) * interval)
+            }
+            Interpolation::Linear => {
+                values[previous].clone() * (1.0 - amount) + values[next].clone() * amount
+            }
+        }
+    }
+
+    fn interpolate_rotation(
+        rotations: &[glm::Quat],
+        interpolation: Interpolation,
+        keyframe: &Keyframe,
+    ) -> glm::Quat {
+        match interpolation {
+            Interpolation::Linear => slerp(
+                rotations[keyframe.previous],
+                rotations[keyframe.next],
+                keyframe.amount,
+            ),
+            _ => Self::interpolate(rotations, interpolation, keyframe).normalize(),
         }
     }
 
@@ -549,7 +1868,7 @@ impl GltfAsset {
                 .neighbors_directed(current_node_index, Outgoing)
                 .detach();
 
-            if let Some(parent) = incoming_walker.next_node(&graph) {
+            if let Some(parent) = incoming_walker.next_node(graph) {
                 while let Some(last_index) = indices.last() {
                     if *last_index == parent {
                         break;
@@ -566,7 +1885,7 @@ impl GltfAsset {
             }
 
             // If the node has no children, don't store the index
-            if outgoing_walker.next(&graph).is_none() {
+            if outgoing_walker.next(graph).is_none() {
                 indices.pop();
             }
         }
@@ -578,27 +1897,25 @@ impl GltfAsset {
         indices
             .iter()
             .fold(glm::Mat4::identity(), |transform, index| {
-                transform
-                    * graph[*index].local_transform
-                    * graph[*index].animation_transform.matrix()
+                transform * graph[*index].local_transform
             })
     }
 
-    pub fn walk<F>(&self, action: F)
+    pub fn walk<F>(&self, mut action: F)
     where
-        F: Fn(NodeIndex, &NodeGraph),
+        F: FnMut(NodeIndex, &NodeGraph),
     {
         for scene in self.scenes.iter() {
             for graph in scene.node_graphs.iter() {
                 let mut dfs = Dfs::new(&graph, NodeIndex::new(0));
                 while let Some(node_index) = dfs.next(&graph) {
-                    action(node_index, &graph);
+                    action(node_index, graph);
                 }
             }
         }
     }
 
-    pub fn create_vertex_attributes() -> [vk::VertexInputAttributeDescription; 6] {
+    pub fn create_vertex_attributes() -> [vk::VertexInputAttributeDescription; 15] {
         let float_size = std::mem::size_of::<f32>();
         let position_description = vk::VertexInputAttributeDescription::builder()
             .binding(0)
@@ -631,17 +1948,55 @@ impl GltfAsset {
         let joint_0_description = vk::VertexInputAttributeDescription::builder()
             .binding(0)
             .location(4)
-            .format(vk::Format::R32G32_SFLOAT)
+            .format(vk::Format::R32G32B32A32_SFLOAT)
             .offset((10 * float_size) as _)
             .build();
 
         let weight_0_description = vk::VertexInputAttributeDescription::builder()
             .binding(0)
             .location(5)
-            .format(vk::Format::R32G32_SFLOAT)
+            .format(vk::Format::R32G32B32A32_SFLOAT)
             .offset((14 * float_size) as _)
             .build();
 
+        let tangent_description = vk::VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(6)
+            .format(vk::Format::R32G32B32A32_SFLOAT)
+            .offset((18 * float_size) as _)
+            .build();
+
+        let color_0_description = vk::VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(7)
+            .format(vk::Format::R32G32B32A32_SFLOAT)
+            .offset((22 * float_size) as _)
+            .build();
+
+        // The instance model matrix is read one column per location
+        let instance_model_descriptions = [0, 1, 2, 3].map(|column| {
+            vk::VertexInputAttributeDescription::builder()
+                .binding(1)
+                .location(8 + column)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(column * std::mem::size_of::<glm::Vec4>() as u32)
+                .build()
+        });
+        let instance_weights_descriptions = [0, 1].map(|index| {
+            vk::VertexInputAttributeDescription::builder()
+                .binding(1)
+                .location(12 + index)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset((16 + index * 4) * float_size as u32)
+                .build()
+        });
+        let instance_joint_offset_description = vk::VertexInputAttributeDescription::builder()
+            .binding(1)
+            .location(14)
+            .format(vk::Format::R32_SINT)
+            .offset((16 + MAX_MORPH_TARGETS) as u32 * float_size as u32)
+            .build();
+
         [
             position_description,
             normal_description,
@@ -649,41 +2004,51 @@ impl GltfAsset {
             tex_coord_1_description,
             joint_0_description,
             weight_0_description,
+            tangent_description,
+            color_0_description,
+            instance_model_descriptions[0],
+            instance_model_descriptions[1],
+            instance_model_descriptions[2],
+            instance_model_descriptions[3],
+            instance_weights_descriptions[0],
+            instance_weights_descriptions[1],
+            instance_joint_offset_description,
         ]
     }
 
-    pub fn create_vertex_input_descriptions() -> [vk::VertexInputBindingDescription; 1] {
+    pub fn create_vertex_input_descriptions() -> [vk::VertexInputBindingDescription; 2] {
         let vertex_input_binding_description = vk::VertexInputBindingDescription::builder()
             .binding(0)
-            .stride((18 * std::mem::size_of::<f32>()) as _)
+            .stride((26 * std::mem::size_of::<f32>()) as _)
             .input_rate(vk::VertexInputRate::VERTEX)
             .build();
-        [vertex_input_binding_description]
+        let instance_input_binding_description = vk::VertexInputBindingDescription::builder()
+            .binding(1)
+            .stride(std::mem::size_of::<ShaderInstance>() as _)
+            .input_rate(vk::VertexInputRate::INSTANCE)
+            .build();
+        [
+            vertex_input_binding_description,
+            instance_input_binding_description,
+        ]
     }
 }
 
-pub struct GltfTextureData {
+pub struct GltfImageData {
     pub texture: Texture,
     pub view: ImageView,
-    pub sampler: Sampler,
 }
 
-impl GltfTextureData {
+impl GltfImageData {
     pub fn new(renderer: &Renderer, image_data: &gltf::image::Data) -> Self {
-        let description = TextureDescription::from_gltf(&image_data);
+        let description = TextureDescription::from_gltf(image_data);
 
         let texture = Self::create_texture(renderer.context.clone(), &description);
         texture.upload_texture_data(&renderer.command_pool, &description);
 
         let view = Self::create_image_view(renderer.context.clone(), &texture, &description);
 
-        let sampler = Self::create_sampler(renderer.context.clone(), description.mip_levels);
-
-        Self {
-            texture,
-            view,
-            sampler,
-        }
+        Self { texture, view }
     }
 
     fn create_texture(context: Arc<VulkanContext>, description: &TextureDescription) -> Texture {
@@ -742,25 +2107,190 @@ impl GltfTextureData {
             .build();
         ImageView::new(context, create_info)
     }
+}
+
+pub struct GltfTextureData {
+    pub image: Arc<GltfImageData>,
+    pub sampler: Arc<Sampler>,
+}
+
+impl GltfTextureData {
+    pub fn new(
+        sampler_cache: &mut SamplerCache,
+        images: &[Arc<GltfImageData>],
+        texture: &gltf::Texture,
+    ) -> Self {
+        let image = images[texture.source().index()].clone();
+        let sampler = sampler_cache.sampler(Self::sampler_create_info(&texture.sampler()));
+        Self { image, sampler }
+    }
+
+    fn sampler_create_info(sampler: &gltf::texture::Sampler) -> vk::SamplerCreateInfo {
+        // Unspecified filters default to trilinear filtering
+        let mag_filter = match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => vk::Filter::NEAREST,
+            Some(MagFilter::Linear) | None => vk::Filter::LINEAR,
+        };
 
-    fn create_sampler(context: Arc<VulkanContext>, mip_levels: u32) -> Sampler {
-        let sampler_info = vk::SamplerCreateInfo::builder()
-            .mag_filter(vk::Filter::LINEAR)
-            .min_filter(vk::Filter::LINEAR)
-            .address_mode_u(vk::SamplerAddressMode::REPEAT)
-            .address_mode_v(vk::SamplerAddressMode::REPEAT)
+        let (min_filter, mipmap_mode, mipmapped) = match sampler.min_filter() {
+            Some(MinFilter::Nearest) => {
+                (vk::Filter::NEAREST, vk::SamplerMipmapMode::NEAREST, false)
+            }
+            Some(MinFilter::Linear) => (vk::Filter::LINEAR, vk::SamplerMipmapMode::NEAREST, false),
+            Some(MinFilter::NearestMipmapNearest) => {
+                (vk::Filter::NEAREST, vk::SamplerMipmapMode::NEAREST, true)
+            }
+            Some(MinFilter::LinearMipmapNearest) => {
+                (vk::Filter::LINEAR, vk::SamplerMipmapMode::NEAREST, true)
+            }
+            Some(MinFilter::NearestMipmapLinear) => {
+                (vk::Filter::NEAREST, vk::SamplerMipmapMode::LINEAR, true)
+            }
+            Some(MinFilter::LinearMipmapLinear) | None => {
+                (vk::Filter::LINEAR, vk::SamplerMipmapMode::LINEAR, true)
+            }
+        };
+
+        // Filters without mipmapping only ever sample the base level
+        let max_lod = if mipmapped { vk::LOD_CLAMP_NONE } else { 0.0 };
+
+        // Anisotropic filtering would blur textures that ask for nearest filtering
+        let anisotropy_enable =
+            mag_filter == vk::Filter::LINEAR && min_filter == vk::Filter::LINEAR;
+
+        vk::SamplerCreateInfo::builder()
+            .mag_filter(mag_filter)
+            .min_filter(min_filter)
+            .address_mode_u(Self::address_mode(sampler.wrap_s()))
+            .address_mode_v(Self::address_mode(sampler.wrap_t()))
             .address_mode_w(vk::SamplerAddressMode::REPEAT)
-            .anisotropy_enable(true)
-            .max_anisotropy(16.0)
+            .anisotropy_enable(anisotropy_enable)
+            .max_anisotropy(if anisotropy_enable { 16.0 } else { 1.0 })
             .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
             .unnormalized_coordinates(false)
             .compare_enable(false)
             .compare_op(vk::CompareOp::ALWAYS)
-            .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+            .mipmap_mode(mipmap_mode)
             .mip_lod_bias(0.0)
             .min_lod(0.0)
-            .max_lod(mip_levels as _)
-            .build();
-        Sampler::new(context, sampler_info)
+            .max_lod(max_lod)
+            .build()
+    }
+
+    fn address_mode(wrapping_mode: WrappingMode) -> vk::SamplerAddressMode {
+        match wrapping_mode {
+            WrappingMode::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            WrappingMode::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
+            WrappingMode::Repeat => vk::SamplerAddressMode::REPEAT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(inputs: &[f32], time: f32) -> Keyframe {
+        let channel = Channel {
+            node_index: 0,
+            inputs: inputs.to_vec(),
+            transformations: TransformationSet::Translations(Vec::new()),
+            interpolation: Interpolation::Linear,
+        };
+        channel.keyframe(time)
+    }
+
+    fn assert_vec3_eq(actual: glm::Vec3, expected: glm::Vec3) {
+        assert!(
+            glm::distance(&actual, &expected) < 1e-5,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn keyframe_finds_the_surrounding_keys() {
+        let keyframe = keyframe(&[0.0, 1.0, 3.0, 4.0], 2.0);
+        assert_eq!((keyframe.previous, keyframe.next), (1, 2));
+        assert_eq!(keyframe.interval, 2.0);
+        assert_eq!(keyframe.amount, 0.5);
+    }
+
+    #[test]
+    fn keyframe_holds_the_first_and_last_keys_outside_the_channel() {
+        let before = keyframe(&[1.0, 2.0, 3.0], 0.0);
+        assert_eq!((before.previous, before.next), (0, 1));
+        assert_eq!(before.amount, 0.0);
+
+        let after = keyframe(&[1.0, 2.0, 3.0], 5.0);
+        assert_eq!((after.previous, after.next), (1, 2));
+        assert_eq!(after.amount, 1.0);
+    }
+
+    #[test]
+    fn keyframe_does_not_depend_on_earlier_samples() {
+        let inputs = [0.0, 1.0, 2.0, 3.0];
+        let late = keyframe(&inputs, 2.5);
+        let early = keyframe(&inputs, 0.5);
+        assert_eq!((late.previous, late.next), (2, 3));
+        assert_eq!((early.previous, early.next), (0, 1));
+    }
+
+    #[test]
+    fn step_holds_the_previous_value() {
+        let values = [glm::vec3(0.0, 0.0, 0.0), glm::vec3(2.0, 4.0, 6.0)];
+        let keyframe = keyframe(&[0.0, 1.0], 0.75);
+        let value = GltfAsset::interpolate(&values, Interpolation::Step, &keyframe);
+        assert_vec3_eq(value, values[0]);
+    }
+
+    #[test]
+    fn linear_blends_between_values() {
+        let values = [glm::vec3(0.0, 0.0, 0.0), glm::vec3(2.0, 4.0, 6.0)];
+        let keyframe = keyframe(&[0.0, 1.0], 0.25);
+        let value = GltfAsset::interpolate(&values, Interpolation::Linear, &keyframe);
+        assert_vec3_eq(value, glm::vec3(0.5, 1.0, 1.5));
+    }
+
+    #[test]
+    fn cubic_spline_uses_the_tangents() {
+        // (in-tangent, value, out-tangent) triplets for each key
+        let values = [
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(1.0, 0.0, 0.0),
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            glm::vec3(0.0, 0.0, 0.0),
+        ];
+        let keyframe = keyframe(&[0.0, 2.0], 1.0);
+        let value = GltfAsset::interpolate(&values, Interpolation::CubicSpline, &keyframe);
+        // The out-tangent is scaled by the two second interval
+        assert_vec3_eq(value, glm::vec3(0.25, 0.5, 0.0));
+    }
+
+    #[test]
+    fn linear_rotations_slerp() {
+        let axis = glm::vec3(0.0, 0.0, 1.0);
+        let rotations = [
+            glm::quat_angle_axis(0.0, &axis),
+            glm::quat_angle_axis(90_f32.to_radians(), &axis),
+        ];
+        let keyframe = keyframe(&[0.0, 1.0], 0.5);
+        let rotation =
+            GltfAsset::interpolate_rotation(&rotations, Interpolation::Linear, &keyframe);
+        let expected = glm::quat_angle_axis(45_f32.to_radians(), &axis);
+        assert!(rotation.dot(&expected).abs() > 1.0 - 1e-5);
+    }
+
+    #[test]
+    fn slerp_takes_the_shortest_path() {
+        let axis = glm::vec3(0.0, 0.0, 1.0);
+        let start = glm::quat_angle_axis(0.0, &axis);
+        let end = -glm::quat_angle_axis(90_f32.to_radians(), &axis);
+        let expected = glm::quat_angle_axis(45_f32.to_radians(), &axis);
+        assert!(slerp(start, end, 0.5).dot(&expected).abs() > 1.0 - 1e-5);
+        assert!(slerp(start, end, 0.5).dot(&start) > 0.0);
     }
 }
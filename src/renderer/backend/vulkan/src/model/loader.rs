@@ -0,0 +1,109 @@
+use crate::model::gltf::GltfAssetData;
+use std::{
+    any::Any,
+    collections::HashSet,
+    panic,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+};
+
+type LoadResult = (String, Result<GltfAssetData, String>);
+
+// Prepares assets on worker threads, leaving only the upload to the render thread
+pub struct AssetLoader {
+    sender: Sender<LoadResult>,
+    receiver: Mutex<Receiver<LoadResult>>,
+    pub pending: Vec<String>,
+    pub failed: HashSet<String>,
+    // Assets that changed on disk while they were loading
+    pub queued: HashSet<String>,
+}
+
+impl Default for AssetLoader {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+            pending: Vec::new(),
+            failed: HashSet::new(),
+            queued: HashSet::new(),
+        }
+    }
+}
+
+impl AssetLoader {
+    pub fn is_pending(&self, asset_name: &str) -> bool {
+        self.pending.iter().any(|pending| pending == asset_name)
+    }
+
+    // Assets that are loading or failed to load aren't loaded again
+    pub fn load(&mut self, asset_name: &str) {
+        if self.is_pending(asset_name) || self.failed.contains(asset_name) {
+            return;
+        }
+        self.pending.push(asset_name.to_string());
+
+        let sender = self.sender.clone();
+        let asset_name = asset_name.to_string();
+        thread::spawn(move || {
+            // A panic while reading the asset fails the load instead of leaving it pending
+            let data = match panic::catch_unwind(|| GltfAssetData::load(&asset_name)) {
+                Ok(data) => data.map_err(|error| error.to_string()),
+                Err(payload) => Err(panic_message(payload.as_ref())),
+            };
+            // The loader may already be gone when the app is closing
+            let _ = sender.send((asset_name, data));
+        });
+    }
+
+    // Loads an asset again after its files changed, even if it failed before
+    pub fn reload(&mut self, asset_name: &str) {
+        self.failed.remove(asset_name);
+        if self.is_pending(asset_name) {
+            self.queued.insert(asset_name.to_string());
+        } else {
+            self.load(asset_name);
+        }
+    }
+
+    // The assets that finished loading since the last call
+    pub fn finished(&mut self) -> Vec<GltfAssetData> {
+        let results = self
+            .receiver
+            .lock()
+            .expect("Failed to lock the asset loader!")
+            .try_iter()
+            .collect::<Vec<_>>();
+
+        let mut finished = Vec::new();
+        for (asset_name, result) in results {
+            self.pending.retain(|pending| *pending != asset_name);
+            if self.queued.remove(&asset_name) {
+                self.load(&asset_name);
+                continue;
+            }
+            match result {
+                Ok(data) => finished.push(data),
+                Err(error) => {
+                    log::warn!("Failed to load asset '{}': {}", asset_name, error);
+                    self.failed.insert(asset_name);
+                }
+            }
+        }
+        finished
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "The loader thread panicked".to_string()
+    }
+}
@@ -1,4 +1,6 @@
 pub mod gltf;
+pub mod loader;
+pub mod watcher;
 use crate::resource::{Buffer, CommandPool};
 use ash::vk;
 
@@ -0,0 +1,63 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+// Checks the modification times of files, which works on every platform
+// and is cheap for the handful of files an app loads
+pub struct FileWatcher {
+    files: HashMap<PathBuf, Option<SystemTime>>,
+    last_poll: Instant,
+    pub poll_interval: Duration,
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self {
+        Self {
+            files: HashMap::new(),
+            last_poll: Instant::now(),
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+impl FileWatcher {
+    // Files that are still watched keep their last known modification time
+    pub fn watch_files(&mut self, paths: Vec<PathBuf>) {
+        let mut files = HashMap::new();
+        for path in paths.into_iter() {
+            let modified = match self.files.remove(&path) {
+                Some(modified) => modified,
+                None => Self::modified(&path),
+            };
+            files.insert(path, modified);
+        }
+        self.files = files;
+    }
+
+    // The files that were modified, created, or removed since the last poll
+    pub fn changed_files(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.poll_interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = Vec::new();
+        for (path, last_modified) in self.files.iter_mut() {
+            let modified = Self::modified(path);
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed.push(path.to_path_buf());
+            }
+        }
+        changed
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
@@ -1,30 +1,446 @@
 use crate::{
     core::VulkanContext,
-    model::gltf::{GltfAsset, GltfTextureData, Primitive},
+    model::gltf::{GltfAsset, GltfTextureData},
     render::{GraphicsPipeline, Renderer},
     resource::{Buffer, DescriptorPool, DescriptorSetLayout, DummyImage, PipelineLayout, Shader},
+    sync::{SynchronizationSet, SynchronizationSetConstants},
 };
 use ash::{version::DeviceV1_0, vk};
-use dragonglass_core::byte_slice_from;
+use dragonglass_core::{
+    byte_slice_from,
+    components::{Light, LightKind},
+};
+use gltf::json::{extensions::texture::TextureTransform, Value};
 use nalgebra_glm as glm;
-use std::{ffi::CString, mem, sync::Arc};
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    hash::{Hash, Hasher},
+    mem,
+    sync::Arc,
+};
+
+// The material push constants follow the primitive push constants
+// and this offset should match the one defined in the fragment shader
+pub const MATERIAL_PUSH_CONSTANT_OFFSET: u32 = 16;
+
+pub struct PushConstantBlockPrimitive {
+    pub first_vertex: i32,
+    pub morph_target_offset: i32,
+    pub number_of_morph_targets: i32,
+    pub joint_offset: i32, // -1 if the primitive is not skinned
+}
 
 pub struct PushConstantBlockMaterial {
+    pub material_index: i32,
+    // Material texture indices are relative to the asset's first texture
+    pub texture_offset: i32,
+}
+
+// A texture in the texture array and how its coordinates are read,
+// and this layout must match the std430 layout in the fragment shader
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ShaderTextureInfo {
+    pub index: i32,         // -1 if there is no texture
+    pub tex_coord_set: i32, // The TEXCOORD_n set the texture is sampled with
+    _padding: [i32; 2],
+    // The rows of the KHR_texture_transform matrix applied to the texture coordinates
+    pub transform: [glm::Vec4; 2],
+}
+
+impl Default for ShaderTextureInfo {
+    fn default() -> Self {
+        Self {
+            index: -1,
+            tex_coord_set: 0,
+            _padding: [0; 2],
+            transform: Self::texture_transform([0.0, 0.0], 0.0, [1.0, 1.0]),
+        }
+    }
+}
+
+impl ShaderTextureInfo {
+    pub fn new(index: usize, tex_coord: u32) -> Self {
+        Self {
+            index: index as i32,
+            tex_coord_set: tex_coord as i32,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_info(info: &gltf::texture::Info) -> Self {
+        let texture_info = Self::new(info.texture().index(), info.tex_coord());
+        match info.texture_transform() {
+            Some(transform) => texture_info.with_transform(
+                transform.offset(),
+                transform.rotation(),
+                transform.scale(),
+                transform.tex_coord(),
+            ),
+            None => texture_info,
+        }
+    }
+
+    // Reads a texture info from the json of an extension that gltf doesn't parse
+    pub fn from_json(info: &Value) -> Option<Self> {
+        let index = info.get("index").and_then(Value::as_u64)?;
+        let tex_coord = info.get("texCoord").and_then(Value::as_u64).unwrap_or(0);
+        let transform = info
+            .get("extensions")
+            .and_then(|extensions| extensions.get(TEXTURE_TRANSFORM_EXTENSION));
+        Some(Self::new(index as usize, tex_coord as u32).with_json_transform(transform))
+    }
+
+    // Normal and occlusion textures have no typed texture transform in gltf,
+    // so their extension json is deserialized into the gltf json type
+    fn with_json_transform(self, transform: Option<&Value>) -> Self {
+        let transform = transform.cloned().and_then(|transform| {
+            gltf::json::deserialize::from_value::<TextureTransform>(transform).ok()
+        });
+        match transform {
+            Some(transform) => self.with_transform(
+                transform.offset.0,
+                transform.rotation.0,
+                transform.scale.0,
+                transform.tex_coord,
+            ),
+            None => self,
+        }
+    }
+
+    // The texture transform extension may override the texture coordinate set
+    fn with_transform(
+        mut self,
+        offset: [f32; 2],
+        rotation: f32,
+        scale: [f32; 2],
+        tex_coord: Option<u32>,
+    ) -> Self {
+        if let Some(tex_coord) = tex_coord {
+            self.tex_coord_set = tex_coord as i32;
+        }
+        self.transform = Self::texture_transform(offset, rotation, scale);
+        self
+    }
+
+    // Texture coordinates are transformed by translation * rotation * scale
+    fn texture_transform(offset: [f32; 2], rotation: f32, scale: [f32; 2]) -> [glm::Vec4; 2] {
+        let (sin, cos) = rotation.sin_cos();
+        [
+            glm::vec4(cos * scale[0], sin * scale[1], offset[0], 0.0),
+            glm::vec4(-sin * scale[0], cos * scale[1], offset[1], 0.0),
+        ]
+    }
+}
+
+// Only the fields are hashed, so padding never affects the material cache key
+impl Hash for ShaderTextureInfo {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.tex_coord_set.hash(state);
+        for row in self.transform.iter() {
+            hash_floats(row.as_slice(), state);
+        }
+    }
+}
+
+// Materials are stored in a storage buffer per asset,
+// and this layout must match the std430 layout in the fragment shader
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ShaderMaterial {
     pub base_color_factor: glm::Vec4,
     pub emissive_factor: glm::Vec3,
-    pub color_texture_set: i32,
-    pub metallic_roughness_texture_set: i32, // B channel - metalness values. G channel - roughness values
-    pub normal_texture_set: i32,
-    pub occlusion_texture_set: i32, // R channel - occlusion values
-    pub emissive_texture_set: i32,
+    pub emissive_strength: f32,
     pub metallic_factor: f32,
     pub roughness_factor: f32,
     pub alpha_mask: i32,
     pub alpha_mask_cutoff: f32,
+    pub unlit: i32, // 1 if the material uses KHR_materials_unlit
+    pub clearcoat_factor: f32,
+    pub clearcoat_roughness_factor: f32,
+    pub sheen_roughness_factor: f32,
+    pub sheen_color_factor: glm::Vec3,
+    pub transmission_factor: f32,
+    pub specular_color_factor: glm::Vec3,
+    pub specular_factor: f32,
+    pub ior: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub clearcoat_normal_scale: f32,
+    pub color_texture: ShaderTextureInfo,
+    pub metallic_roughness_texture: ShaderTextureInfo, // B channel - metalness values. G channel - roughness values
+    pub normal_texture: ShaderTextureInfo,
+    pub occlusion_texture: ShaderTextureInfo, // R channel - occlusion values
+    pub emissive_texture: ShaderTextureInfo,
+    pub clearcoat_texture: ShaderTextureInfo, // R channel - clearcoat values
+    pub clearcoat_roughness_texture: ShaderTextureInfo, // G channel - clearcoat roughness values
+    pub clearcoat_normal_texture: ShaderTextureInfo,
+    pub sheen_color_texture: ShaderTextureInfo,
+    pub sheen_roughness_texture: ShaderTextureInfo, // A channel - sheen roughness values
+    pub transmission_texture: ShaderTextureInfo,    // R channel - transmission values
+    pub specular_texture: ShaderTextureInfo,        // A channel - specular values
+    pub specular_color_texture: ShaderTextureInfo,
+}
+
+impl Hash for ShaderMaterial {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_floats(self.base_color_factor.as_slice(), state);
+        hash_floats(self.emissive_factor.as_slice(), state);
+        hash_floats(
+            &[
+                self.emissive_strength,
+                self.metallic_factor,
+                self.roughness_factor,
+                self.alpha_mask_cutoff,
+                self.clearcoat_factor,
+                self.clearcoat_roughness_factor,
+                self.sheen_roughness_factor,
+                self.transmission_factor,
+                self.specular_factor,
+                self.ior,
+                self.normal_scale,
+                self.occlusion_strength,
+                self.clearcoat_normal_scale,
+            ],
+            state,
+        );
+        hash_floats(self.sheen_color_factor.as_slice(), state);
+        hash_floats(self.specular_color_factor.as_slice(), state);
+        self.alpha_mask.hash(state);
+        self.unlit.hash(state);
+        for texture in [
+            &self.color_texture,
+            &self.metallic_roughness_texture,
+            &self.normal_texture,
+            &self.occlusion_texture,
+            &self.emissive_texture,
+            &self.clearcoat_texture,
+            &self.clearcoat_roughness_texture,
+            &self.clearcoat_normal_texture,
+            &self.sheen_color_texture,
+            &self.sheen_roughness_texture,
+            &self.transmission_texture,
+            &self.specular_texture,
+            &self.specular_color_texture,
+        ]
+        .iter()
+        {
+            texture.hash(state);
+        }
+    }
+}
+
+// Primitives without a material use the glTF default material
+impl Default for ShaderMaterial {
+    fn default() -> Self {
+        Self {
+            base_color_factor: glm::vec4(1.0, 1.0, 1.0, 1.0),
+            emissive_factor: glm::vec3(0.0, 0.0, 0.0),
+            emissive_strength: 1.0,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            alpha_mask: gltf::material::AlphaMode::Opaque as i32,
+            alpha_mask_cutoff: 0.5,
+            unlit: 0,
+            clearcoat_factor: 0.0,
+            clearcoat_roughness_factor: 0.0,
+            sheen_roughness_factor: 0.0,
+            sheen_color_factor: glm::vec3(0.0, 0.0, 0.0),
+            transmission_factor: 0.0,
+            specular_color_factor: glm::vec3(1.0, 1.0, 1.0),
+            specular_factor: 1.0,
+            ior: 1.5,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            clearcoat_normal_scale: 1.0,
+            color_texture: ShaderTextureInfo::default(),
+            metallic_roughness_texture: ShaderTextureInfo::default(),
+            normal_texture: ShaderTextureInfo::default(),
+            occlusion_texture: ShaderTextureInfo::default(),
+            emissive_texture: ShaderTextureInfo::default(),
+            clearcoat_texture: ShaderTextureInfo::default(),
+            clearcoat_roughness_texture: ShaderTextureInfo::default(),
+            clearcoat_normal_texture: ShaderTextureInfo::default(),
+            sheen_color_texture: ShaderTextureInfo::default(),
+            sheen_roughness_texture: ShaderTextureInfo::default(),
+            transmission_texture: ShaderTextureInfo::default(),
+            specular_texture: ShaderTextureInfo::default(),
+            specular_color_texture: ShaderTextureInfo::default(),
+        }
+    }
+}
+
+impl ShaderMaterial {
+    pub fn from_gltf(primitive_material: &gltf::Material) -> Self {
+        let mut material = Self::default();
+        let pbr = primitive_material.pbr_metallic_roughness();
+
+        material.base_color_factor = glm::Vec4::from(pbr.base_color_factor());
+        material.metallic_factor = pbr.metallic_factor();
+        material.roughness_factor = pbr.roughness_factor();
+        material.emissive_factor = glm::Vec3::from(primitive_material.emissive_factor());
+        material.alpha_mask_cutoff = primitive_material.alpha_cutoff().unwrap_or(0.5);
+        material.alpha_mask = primitive_material.alpha_mode() as i32;
+        material.unlit = primitive_material.unlit() as i32;
+        material.emissive_strength = primitive_material.emissive_strength().unwrap_or(1.0);
+        material.ior = primitive_material.ior().unwrap_or(1.5);
+
+        if let Some(base_color_texture) = pbr.base_color_texture() {
+            material.color_texture = ShaderTextureInfo::from_info(&base_color_texture);
+        }
+
+        if let Some(metallic_roughness_texture) = pbr.metallic_roughness_texture() {
+            material.metallic_roughness_texture =
+                ShaderTextureInfo::from_info(&metallic_roughness_texture);
+        }
+
+        if let Some(normal_texture) = primitive_material.normal_texture() {
+            material.normal_texture = ShaderTextureInfo::new(
+                normal_texture.texture().index(),
+                normal_texture.tex_coord(),
+            )
+            .with_json_transform(normal_texture.extension_value(TEXTURE_TRANSFORM_EXTENSION));
+            material.normal_scale = normal_texture.scale();
+        }
+
+        if let Some(occlusion_texture) = primitive_material.occlusion_texture() {
+            material.occlusion_texture = ShaderTextureInfo::new(
+                occlusion_texture.texture().index(),
+                occlusion_texture.tex_coord(),
+            )
+            .with_json_transform(occlusion_texture.extension_value(TEXTURE_TRANSFORM_EXTENSION));
+            material.occlusion_strength = occlusion_texture.strength();
+        }
+
+        if let Some(emissive_texture) = primitive_material.emissive_texture() {
+            material.emissive_texture = ShaderTextureInfo::from_info(&emissive_texture);
+        }
+
+        if let Some(transmission) = primitive_material.transmission() {
+            material.transmission_factor = transmission.transmission_factor();
+            if let Some(transmission_texture) = transmission.transmission_texture() {
+                material.transmission_texture = ShaderTextureInfo::from_info(&transmission_texture);
+            }
+        }
+
+        if let Some(specular) = primitive_material.specular() {
+            material.specular_factor = specular.specular_factor();
+            material.specular_color_factor = glm::Vec3::from(specular.specular_color_factor());
+            if let Some(specular_texture) = specular.specular_texture() {
+                material.specular_texture = ShaderTextureInfo::from_info(&specular_texture);
+            }
+            if let Some(specular_color_texture) = specular.specular_color_texture() {
+                material.specular_color_texture =
+                    ShaderTextureInfo::from_info(&specular_color_texture);
+            }
+        }
+
+        // Clearcoat and sheen aren't parsed by gltf, so they are read from the extension json
+        if let Some(clearcoat) = primitive_material.extension_value("KHR_materials_clearcoat") {
+            let clearcoat = Some(clearcoat);
+            material.clearcoat_factor = json_f32(clearcoat, "clearcoatFactor", 0.0);
+            material.clearcoat_roughness_factor =
+                json_f32(clearcoat, "clearcoatRoughnessFactor", 0.0);
+            material.clearcoat_texture = json_texture_info(clearcoat, "clearcoatTexture");
+            material.clearcoat_roughness_texture =
+                json_texture_info(clearcoat, "clearcoatRoughnessTexture");
+            material.clearcoat_normal_texture =
+                json_texture_info(clearcoat, "clearcoatNormalTexture");
+            material.clearcoat_normal_scale = json_f32(
+                clearcoat.and_then(|clearcoat| clearcoat.get("clearcoatNormalTexture")),
+                "scale",
+                1.0,
+            );
+        }
+
+        if let Some(sheen) = primitive_material.extension_value("KHR_materials_sheen") {
+            let sheen = Some(sheen);
+            material.sheen_color_factor = json_vec3(sheen, "sheenColorFactor", 0.0);
+            material.sheen_roughness_factor = json_f32(sheen, "sheenRoughnessFactor", 0.0);
+            material.sheen_color_texture = json_texture_info(sheen, "sheenColorTexture");
+            material.sheen_roughness_texture = json_texture_info(sheen, "sheenRoughnessTexture");
+        }
+
+        material
+    }
+}
+
+// Floats are hashed by their bits, which is enough to find identical materials
+fn hash_floats<H: Hasher>(values: &[f32], state: &mut H) {
+    for value in values.iter() {
+        value.to_bits().hash(state);
+    }
+}
+
+const TEXTURE_TRANSFORM_EXTENSION: &str = "KHR_texture_transform";
+
+fn json_f32(value: Option<&Value>, name: &str, default: f32) -> f32 {
+    value
+        .and_then(|value| value.get(name))
+        .and_then(Value::as_f64)
+        .map_or(default, |value| value as f32)
+}
+
+fn json_component(value: Option<&Value>, name: &str, index: usize, default: f32) -> f32 {
+    value
+        .and_then(|value| value.get(name))
+        .and_then(|values| values.get(index))
+        .and_then(Value::as_f64)
+        .map_or(default, |value| value as f32)
+}
+
+fn json_vec3(value: Option<&Value>, name: &str, default: f32) -> glm::Vec3 {
+    glm::vec3(
+        json_component(value, name, 0, default),
+        json_component(value, name, 1, default),
+        json_component(value, name, 2, default),
+    )
+}
+
+fn json_texture_info(value: Option<&Value>, name: &str) -> ShaderTextureInfo {
+    value
+        .and_then(|value| value.get(name))
+        .and_then(ShaderTextureInfo::from_json)
+        .unwrap_or_default()
+}
+
+const TOPOLOGIES: [vk::PrimitiveTopology; 6] = [
+    vk::PrimitiveTopology::POINT_LIST,
+    vk::PrimitiveTopology::LINE_LIST,
+    vk::PrimitiveTopology::LINE_STRIP,
+    vk::PrimitiveTopology::TRIANGLE_LIST,
+    vk::PrimitiveTopology::TRIANGLE_STRIP,
+    vk::PrimitiveTopology::TRIANGLE_FAN,
+];
+
+// Mirrored nodes are drawn with the opposite front face
+const FRONT_FACES: [vk::FrontFace; 2] =
+    [vk::FrontFace::COUNTER_CLOCKWISE, vk::FrontFace::CLOCKWISE];
+
+// Back faces are only culled for materials that aren't double sided
+const CULL_MODES: [vk::CullModeFlags; 2] = [vk::CullModeFlags::NONE, vk::CullModeFlags::BACK];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PbrPipelineVariant {
+    pub topology: vk::PrimitiveTopology,
+    pub front_face: vk::FrontFace,
+    pub cull_mode: vk::CullModeFlags,
+}
+
+impl Default for PbrPipelineVariant {
+    fn default() -> Self {
+        Self {
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            cull_mode: vk::CullModeFlags::BACK,
+        }
+    }
 }
 
 pub struct PbrPipeline {
-    pub pipeline: GraphicsPipeline,
+    pub pipelines: HashMap<PbrPipelineVariant, GraphicsPipeline>,
 }
 
 impl PbrPipeline {
@@ -33,6 +449,49 @@ impl PbrPipeline {
             Self::create_shaders(renderer.context.clone());
         let shader_state_info = [vertex_shader.state_info(), fragment_shader.state_info()];
 
+        // The variants only differ in fixed function state, so they share one layout
+        let descriptor_set_layout = Arc::new(PbrPipelineData::descriptor_set_layout(
+            renderer.context.clone(),
+        ));
+        let asset_descriptor_set_layout =
+            PbrPipelineData::asset_descriptor_set_layout(renderer.context.clone());
+        let pipeline_layout = Arc::new(Self::create_pipeline_layout(
+            renderer.context.clone(),
+            &descriptor_set_layout,
+            &asset_descriptor_set_layout,
+        ));
+
+        let mut pipelines = HashMap::new();
+        for topology in TOPOLOGIES.iter() {
+            for front_face in FRONT_FACES.iter() {
+                for cull_mode in CULL_MODES.iter() {
+                    let variant = PbrPipelineVariant {
+                        topology: *topology,
+                        front_face: *front_face,
+                        cull_mode: *cull_mode,
+                    };
+                    let pipeline = Self::create_pipeline(
+                        renderer,
+                        &shader_state_info,
+                        variant,
+                        &pipeline_layout,
+                        &descriptor_set_layout,
+                    );
+                    pipelines.insert(variant, pipeline);
+                }
+            }
+        }
+
+        Self { pipelines }
+    }
+
+    fn create_pipeline(
+        renderer: &Renderer,
+        shader_state_info: &[vk::PipelineShaderStageCreateInfo],
+        variant: PbrPipelineVariant,
+        pipeline_layout: &Arc<PipelineLayout>,
+        descriptor_set_layout: &Arc<DescriptorSetLayout>,
+    ) -> GraphicsPipeline {
         let descriptions = GltfAsset::create_vertex_input_descriptions();
         let attributes = GltfAsset::create_vertex_attributes();
         let vertex_input_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
@@ -41,7 +500,7 @@ impl PbrPipeline {
             .build();
 
         let input_assembly_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+            .topology(variant.topology)
             .primitive_restart_enable(false)
             .build();
 
@@ -50,8 +509,8 @@ impl PbrPipeline {
             .rasterizer_discard_enable(false)
             .polygon_mode(vk::PolygonMode::FILL)
             .line_width(1.0)
-            .cull_mode(vk::CullModeFlags::NONE)
-            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .cull_mode(variant.cull_mode)
+            .front_face(variant.front_face)
             .depth_bias_enable(false)
             .depth_bias_constant_factor(0.0)
             .depth_bias_clamp(0.0)
@@ -86,11 +545,6 @@ impl PbrPipeline {
             .blend_constants([0.0, 0.0, 0.0, 0.0])
             .build();
 
-        let descriptor_set_layout =
-            PbrPipelineData::descriptor_set_layout(renderer.context.clone());
-        let pipeline_layout =
-            Self::create_pipeline_layout(renderer.context.clone(), &descriptor_set_layout);
-
         let mut viewport_create_info = vk::PipelineViewportStateCreateInfo::default();
         viewport_create_info.viewport_count = 1;
         viewport_create_info.scissor_count = 1;
@@ -102,7 +556,7 @@ impl PbrPipeline {
             .build();
 
         let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
-            .stages(&shader_state_info)
+            .stages(shader_state_info)
             .vertex_input_state(&vertex_input_create_info)
             .input_assembly_state(&input_assembly_create_info)
             .rasterization_state(&rasterizer_create_info)
@@ -116,14 +570,17 @@ impl PbrPipeline {
             .subpass(0)
             .build();
 
-        let pipeline = GraphicsPipeline::new(
+        GraphicsPipeline::with_shared_layout(
             renderer.context.clone(),
             pipeline_create_info,
-            pipeline_layout,
-            descriptor_set_layout,
-        );
+            pipeline_layout.clone(),
+            descriptor_set_layout.clone(),
+        )
+    }
 
-        Self { pipeline }
+    // All variants share the same pipeline layout
+    pub fn layout(&self) -> vk::PipelineLayout {
+        self.pipelines[&PbrPipelineVariant::default()].layout()
     }
 
     fn create_shaders(context: Arc<VulkanContext>) -> (Shader, Shader, CString) {
@@ -166,14 +623,23 @@ impl PbrPipeline {
     pub fn create_pipeline_layout(
         context: Arc<VulkanContext>,
         descriptor_set_layout: &DescriptorSetLayout,
+        asset_descriptor_set_layout: &DescriptorSetLayout,
     ) -> PipelineLayout {
-        let descriptor_set_layouts = [descriptor_set_layout.layout()];
+        let descriptor_set_layouts = [
+            descriptor_set_layout.layout(),
+            asset_descriptor_set_layout.layout(),
+        ];
 
-        let push_constant_range = vk::PushConstantRange::builder()
-            .stage_flags(vk::ShaderStageFlags::ALL_GRAPHICS)
+        let primitive_push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .size(mem::size_of::<PushConstantBlockPrimitive>() as u32)
+            .build();
+        let material_push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .offset(MATERIAL_PUSH_CONSTANT_OFFSET)
             .size(mem::size_of::<PushConstantBlockMaterial>() as u32)
             .build();
-        let push_constant_ranges = [push_constant_range];
+        let push_constant_ranges = [primitive_push_constant_range, material_push_constant_range];
 
         let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
             .set_layouts(&descriptor_set_layouts)
@@ -182,21 +648,72 @@ impl PbrPipeline {
 
         PipelineLayout::new(context, pipeline_layout_create_info)
     }
+}
 
-    pub fn bind(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
-        unsafe {
-            device.cmd_bind_pipeline(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline.pipeline(),
-            );
+// This should match the number of textures defined in the shader
+pub const MAX_TEXTURES: u32 = 100;
+
+// This should match the number of morph target weights defined in the shader
+pub const MAX_MORPH_TARGETS: usize = 8;
+
+pub const MAX_LIGHTS: usize = 64;
+
+// The light buffer starts with the number of lights,
+// padded to the alignment of the light array that follows it
+pub const LIGHT_BUFFER_HEADER_SIZE: usize = 16;
+
+pub const DIRECTIONAL_LIGHT: i32 = 0;
+pub const POINT_LIGHT: i32 = 1;
+pub const SPOT_LIGHT: i32 = 2;
+
+// This layout must match the std430 layout in the fragment shader
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ShaderLight {
+    pub direction: glm::Vec3,
+    pub range: f32, // 0 if the light has an infinite range
+    pub color: glm::Vec3,
+    pub intensity: f32,
+    pub position: glm::Vec3,
+    pub kind: i32,
+    pub inner_cone_cos: f32,
+    pub outer_cone_cos: f32,
+    _padding: [f32; 2],
+}
+
+impl ShaderLight {
+    pub fn new(light: &Light, transform: &glm::Mat4) -> Self {
+        let (kind, inner_cone_cos, outer_cone_cos) = match light.kind {
+            LightKind::Directional => (DIRECTIONAL_LIGHT, 0.0, 0.0),
+            LightKind::Point => (POINT_LIGHT, 0.0, 0.0),
+            LightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => (SPOT_LIGHT, inner_cone_angle.cos(), outer_cone_angle.cos()),
+        };
+
+        // The vertex shader flips the y axis of world positions
+        let flip = glm::vec3(1.0, -1.0, 1.0);
+        let position = transform.column(3).xyz().component_mul(&flip);
+        let direction = (transform * glm::vec4(0.0, 0.0, -1.0, 0.0))
+            .xyz()
+            .component_mul(&flip)
+            .normalize();
+
+        Self {
+            direction,
+            range: light.range.unwrap_or(0.0),
+            color: light.color,
+            intensity: light.intensity,
+            position,
+            kind,
+            inner_cone_cos,
+            outer_cone_cos,
+            _padding: [0.0; 2],
         }
     }
 }
 
-// This should match the number of textures defined in the shader
-const MAX_TEXTURES: u32 = 100;
-
 #[derive(Debug, Clone, Copy)]
 pub struct UniformBufferObject {
     pub view: glm::Mat4,
@@ -204,77 +721,143 @@ pub struct UniformBufferObject {
     pub cameraposition: glm::Vec3,
 }
 
+// The number of visible instances of a mesh in each winding order.
+// Mirrored instances have a negative determinant and wind clockwise.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VisibleInstances {
+    pub counter_clockwise: u32,
+    pub clockwise: u32,
+}
+
+impl VisibleInstances {
+    pub fn is_empty(&self) -> bool {
+        self.counter_clockwise == 0 && self.clockwise == 0
+    }
+}
+
+// The per instance vertex attributes, which must match the vertex shader.
+// Instances of an entity share its morph target weights and joint matrices.
 #[derive(Debug, Clone, Copy)]
-pub struct DynamicUniformBufferObject {
+#[repr(C)]
+pub struct ShaderInstance {
     pub model: glm::Mat4,
+    pub morph_target_weights: [f32; MAX_MORPH_TARGETS],
+    pub joint_offset: i32,
+    _padding: [i32; 3],
+}
+
+impl ShaderInstance {
+    pub fn new(model: glm::Mat4, weights: &[f32], joint_offset: i32) -> Self {
+        let mut morph_target_weights = [0.0; MAX_MORPH_TARGETS];
+        morph_target_weights
+            .iter_mut()
+            .zip(weights.iter())
+            .for_each(|(target_weight, weight)| *target_weight = *weight);
+        Self {
+            model,
+            morph_target_weights,
+            joint_offset,
+            _padding: [0; 3],
+        }
+    }
 }
 
 pub struct PbrPipelineData {
     pub descriptor_pool: DescriptorPool,
-    pub uniform_buffer: Buffer,
-    pub dynamic_uniform_buffer: Buffer,
-    pub dynamic_alignment: u64,
-    pub descriptor_set: vk::DescriptorSet,
+    // Each frame in flight reads its own uniforms and lights
+    pub uniform_buffers: Vec<Buffer>,
+    // Each frame in flight culls into its own instance buffer
+    pub instance_buffers: Vec<Buffer>,
+    // The number of instances the instance buffers have room for
+    pub instance_capacity: usize,
+    pub light_buffers: Vec<Buffer>,
+    pub descriptor_sets: Vec<vk::DescriptorSet>,
+    pub asset_descriptor_sets: Vec<vk::DescriptorSet>,
     pub dummy: DummyImage,
 }
 
 impl PbrPipelineData {
-    pub fn new(
-        renderer: &Renderer,
-        number_of_meshes: usize,
-        textures: &[&GltfTextureData],
-    ) -> Self {
-        let descriptor_set_layout = Self::descriptor_set_layout(renderer.context.clone());
-        let descriptor_pool = Self::create_descriptor_pool(renderer.context.clone());
-        let descriptor_set =
-            descriptor_pool.allocate_descriptor_sets(descriptor_set_layout.layout(), 1)[0];
+    pub fn new(renderer: &Renderer, assets: &[GltfAsset]) -> Self {
+        let number_of_instance_transforms = assets
+            .iter()
+            .map(|asset| asset.number_of_instance_transforms())
+            .sum::<usize>();
 
-        let uniform_buffer = Buffer::new_mapped_basic(
-            renderer.context.clone(),
-            mem::size_of::<UniformBufferObject>() as _,
-            vk::BufferUsageFlags::UNIFORM_BUFFER,
-            vk_mem::MemoryUsage::CpuToGpu,
+        let textures = assets
+            .iter()
+            .flat_map(|asset| &asset.textures)
+            .collect::<Vec<_>>();
+
+        let descriptor_set_layout = Self::descriptor_set_layout(renderer.context.clone());
+        let asset_descriptor_set_layout =
+            Self::asset_descriptor_set_layout(renderer.context.clone());
+        let descriptor_pool =
+            Self::create_descriptor_pool(renderer.context.clone(), assets.len() as u32);
+        let descriptor_sets = descriptor_pool.allocate_descriptor_sets(
+            descriptor_set_layout.layout(),
+            SynchronizationSet::MAX_FRAMES_IN_FLIGHT as _,
         );
+        let asset_descriptor_sets = if assets.is_empty() {
+            Vec::new()
+        } else {
+            descriptor_pool
+                .allocate_descriptor_sets(asset_descriptor_set_layout.layout(), assets.len() as u32)
+        };
 
-        let dynamic_alignment = Self::calculate_dynamic_alignment(renderer.context.clone());
+        let uniform_buffers = (0..SynchronizationSet::MAX_FRAMES_IN_FLIGHT)
+            .map(|_| {
+                Buffer::new_mapped_basic(
+                    renderer.context.clone(),
+                    mem::size_of::<UniformBufferObject>() as _,
+                    vk::BufferUsageFlags::UNIFORM_BUFFER,
+                    vk_mem::MemoryUsage::CpuToGpu,
+                )
+            })
+            .collect::<Vec<_>>();
 
-        let dynamic_uniform_buffer = Buffer::new_mapped_basic(
-            renderer.context.clone(),
-            (number_of_meshes as u64 * dynamic_alignment) as vk::DeviceSize,
-            vk::BufferUsageFlags::UNIFORM_BUFFER,
-            vk_mem::MemoryUsage::CpuToGpu,
-        );
+        // Vertex buffers can't be empty, so the instance buffers hold at least one instance.
+        // They have room to spare so that new entities rarely need new buffers.
+        let instance_capacity = number_of_instance_transforms.max(1).next_power_of_two();
+        let instance_buffers = (0..SynchronizationSet::MAX_FRAMES_IN_FLIGHT)
+            .map(|_| {
+                Buffer::new_mapped_basic(
+                    renderer.context.clone(),
+                    (instance_capacity * mem::size_of::<ShaderInstance>()) as _,
+                    vk::BufferUsageFlags::VERTEX_BUFFER,
+                    vk_mem::MemoryUsage::CpuToGpu,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let light_buffers = (0..SynchronizationSet::MAX_FRAMES_IN_FLIGHT)
+            .map(|_| {
+                Buffer::new_mapped_basic(
+                    renderer.context.clone(),
+                    (LIGHT_BUFFER_HEADER_SIZE + MAX_LIGHTS * mem::size_of::<ShaderLight>()) as _,
+                    vk::BufferUsageFlags::STORAGE_BUFFER,
+                    vk_mem::MemoryUsage::CpuToGpu,
+                )
+            })
+            .collect::<Vec<_>>();
 
         let data = PbrPipelineData {
             descriptor_pool,
-            uniform_buffer,
-            dynamic_uniform_buffer,
-            descriptor_set,
-            dynamic_alignment,
+            uniform_buffers,
+            instance_buffers,
+            instance_capacity,
+            light_buffers,
+            descriptor_sets,
+            asset_descriptor_sets,
             dummy: DummyImage::new(renderer.context.clone(), &renderer.transient_command_pool),
         };
 
-        data.update_descriptor_set(
-            &renderer,
-            renderer.context.clone(),
-            number_of_meshes,
-            &textures,
-        );
+        for frame in 0..SynchronizationSet::MAX_FRAMES_IN_FLIGHT {
+            data.update_descriptor_set(&renderer, renderer.context.clone(), &textures, frame as _);
+        }
 
-        data
-    }
+        data.update_asset_descriptor_sets(renderer.context.clone(), assets);
 
-    fn calculate_dynamic_alignment(context: Arc<VulkanContext>) -> u64 {
-        let minimum_ubo_alignment = context
-            .physical_device_properties()
-            .limits
-            .min_uniform_buffer_offset_alignment;
-        let dynamic_alignment = std::mem::size_of::<DynamicUniformBufferObject>() as u64;
-        if minimum_ubo_alignment > 0 {
-            (dynamic_alignment + minimum_ubo_alignment - 1) & !(minimum_ubo_alignment - 1)
-        } else {
-            dynamic_alignment
-        }
+        data
     }
 
     pub fn descriptor_set_layout(context: Arc<VulkanContext>) -> DescriptorSetLayout {
@@ -284,12 +867,6 @@ impl PbrPipelineData {
             .descriptor_count(1)
             .stage_flags(vk::ShaderStageFlags::VERTEX)
             .build();
-        let dynamic_ubo_binding = vk::DescriptorSetLayoutBinding::builder()
-            .binding(1)
-            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
-            .descriptor_count(1)
-            .stage_flags(vk::ShaderStageFlags::VERTEX)
-            .build();
         let sampler_binding = vk::DescriptorSetLayoutBinding::builder()
             .binding(2)
             .descriptor_count(MAX_TEXTURES)
@@ -315,13 +892,20 @@ impl PbrPipelineData {
             .stage_flags(vk::ShaderStageFlags::FRAGMENT)
             .build();
 
+        let light_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(6)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+
         let bindings = [
             ubo_binding,
-            dynamic_ubo_binding,
             sampler_binding,
             irradiance_cubemap_binding,
             prefilter_cubemap_binding,
             brdflut_binding,
+            light_binding,
         ];
 
         let layout_create_info = vk::DescriptorSetLayoutCreateInfo::builder()
@@ -330,49 +914,92 @@ impl PbrPipelineData {
         DescriptorSetLayout::new(context, layout_create_info)
     }
 
-    fn create_descriptor_pool(context: Arc<VulkanContext>) -> DescriptorPool {
+    pub fn asset_descriptor_set_layout(context: Arc<VulkanContext>) -> DescriptorSetLayout {
+        let morph_target_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .build();
+
+        let joint_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(1)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .build();
+
+        let material_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(2)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+
+        let bindings = [morph_target_binding, joint_binding, material_binding];
+
+        let layout_create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .bindings(&bindings)
+            .build();
+        DescriptorSetLayout::new(context, layout_create_info)
+    }
+
+    fn create_descriptor_pool(
+        context: Arc<VulkanContext>,
+        number_of_assets: u32,
+    ) -> DescriptorPool {
+        // Each frame in flight has its own set of the shared bindings
+        let number_of_frames = SynchronizationSet::MAX_FRAMES_IN_FLIGHT;
+
         let ubo_pool_size = vk::DescriptorPoolSize {
             ty: vk::DescriptorType::UNIFORM_BUFFER,
-            descriptor_count: 1,
-        };
-
-        let dynamic_ubo_pool_size = vk::DescriptorPoolSize {
-            ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-            descriptor_count: 1,
+            descriptor_count: number_of_frames,
         };
 
         let sampler_pool_size = vk::DescriptorPoolSize {
             ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-            descriptor_count: MAX_TEXTURES,
+            descriptor_count: MAX_TEXTURES * number_of_frames,
         };
 
         let irradiance_cubemap_pool_size = vk::DescriptorPoolSize {
             ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-            descriptor_count: 1,
+            descriptor_count: number_of_frames,
         };
 
         let prefilter_cubemap_pool_size = vk::DescriptorPoolSize {
             ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-            descriptor_count: 1,
+            descriptor_count: number_of_frames,
         };
 
         let brdflut_pool_size = vk::DescriptorPoolSize {
             ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-            descriptor_count: 1,
+            descriptor_count: number_of_frames,
+        };
+
+        let light_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: number_of_frames,
+        };
+
+        // Each asset has its own morph target, joint, and material storage buffers
+        let asset_storage_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: 3 * number_of_assets.max(1),
         };
 
         let pool_sizes = [
             ubo_pool_size,
-            dynamic_ubo_pool_size,
             sampler_pool_size,
             irradiance_cubemap_pool_size,
             prefilter_cubemap_pool_size,
             brdflut_pool_size,
+            light_pool_size,
+            asset_storage_pool_size,
         ];
 
         let pool_info = vk::DescriptorPoolCreateInfo::builder()
             .pool_sizes(&pool_sizes)
-            .max_sets(1)
+            .max_sets(number_of_frames + number_of_assets)
             .build();
 
         DescriptorPool::new(context, pool_info)
@@ -382,32 +1009,25 @@ impl PbrPipelineData {
         &self,
         renderer: &Renderer,
         context: Arc<VulkanContext>,
-        number_of_meshes: usize,
         textures: &[&GltfTextureData],
+        frame: usize,
     ) {
+        let descriptor_set = self.descriptor_sets[frame];
+
         let uniform_buffer_size = mem::size_of::<UniformBufferObject>() as vk::DeviceSize;
         let buffer_info = vk::DescriptorBufferInfo::builder()
-            .buffer(self.uniform_buffer.buffer())
+            .buffer(self.uniform_buffers[frame].buffer())
             .offset(0)
             .range(uniform_buffer_size)
             .build();
         let buffer_infos = [buffer_info];
 
-        let dynamic_uniform_buffer_size =
-            (number_of_meshes as u64 * self.dynamic_alignment) as vk::DeviceSize;
-        let dynamic_buffer_info = vk::DescriptorBufferInfo::builder()
-            .buffer(self.dynamic_uniform_buffer.buffer())
-            .offset(0)
-            .range(dynamic_uniform_buffer_size)
-            .build();
-        let dynamic_buffer_infos = [dynamic_buffer_info];
-
         let mut image_infos = textures
             .iter()
             .map(|texture| {
                 vk::DescriptorImageInfo::builder()
                     .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                    .image_view(texture.view.view())
+                    .image_view(texture.image.view.view())
                     .sampler(texture.sampler.sampler())
                     .build()
             })
@@ -459,23 +1079,15 @@ impl PbrPipelineData {
         let brdflut_image_infos = [brdflut_image_info];
 
         let ubo_descriptor_write = vk::WriteDescriptorSet::builder()
-            .dst_set(self.descriptor_set)
+            .dst_set(descriptor_set)
             .dst_binding(0)
             .dst_array_element(0)
             .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
             .buffer_info(&buffer_infos)
             .build();
 
-        let dynamic_ubo_descriptor_write = vk::WriteDescriptorSet::builder()
-            .dst_set(self.descriptor_set)
-            .dst_binding(1)
-            .dst_array_element(0)
-            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
-            .buffer_info(&dynamic_buffer_infos)
-            .build();
-
         let sampler_descriptor_write = vk::WriteDescriptorSet::builder()
-            .dst_set(self.descriptor_set)
+            .dst_set(descriptor_set)
             .dst_binding(2)
             .dst_array_element(0)
             .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
@@ -483,7 +1095,7 @@ impl PbrPipelineData {
             .build();
 
         let irradiance_cubemap_descriptor_write = vk::WriteDescriptorSet::builder()
-            .dst_set(self.descriptor_set)
+            .dst_set(descriptor_set)
             .dst_binding(3)
             .dst_array_element(0)
             .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
@@ -491,7 +1103,7 @@ impl PbrPipelineData {
             .build();
 
         let prefilter_cubemap_descriptor_write = vk::WriteDescriptorSet::builder()
-            .dst_set(self.descriptor_set)
+            .dst_set(descriptor_set)
             .dst_binding(4)
             .dst_array_element(0)
             .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
@@ -499,20 +1111,35 @@ impl PbrPipelineData {
             .build();
 
         let brdflut_descriptor_write = vk::WriteDescriptorSet::builder()
-            .dst_set(self.descriptor_set)
+            .dst_set(descriptor_set)
             .dst_binding(5)
             .dst_array_element(0)
             .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
             .image_info(&brdflut_image_infos)
             .build();
 
+        let light_buffer_info = vk::DescriptorBufferInfo::builder()
+            .buffer(self.light_buffers[frame].buffer())
+            .offset(0)
+            .range(vk::WHOLE_SIZE)
+            .build();
+        let light_buffer_infos = [light_buffer_info];
+
+        let light_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(6)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .buffer_info(&light_buffer_infos)
+            .build();
+
         let descriptor_writes = vec![
             ubo_descriptor_write,
-            dynamic_ubo_descriptor_write,
             sampler_descriptor_write,
             irradiance_cubemap_descriptor_write,
             prefilter_cubemap_descriptor_write,
             brdflut_descriptor_write,
+            light_descriptor_write,
         ];
 
         unsafe {
@@ -522,13 +1149,77 @@ impl PbrPipelineData {
                 .update_descriptor_sets(&descriptor_writes, &[])
         }
     }
+
+    fn update_asset_descriptor_sets(&self, context: Arc<VulkanContext>, assets: &[GltfAsset]) {
+        for (asset, descriptor_set) in assets.iter().zip(self.asset_descriptor_sets.iter()) {
+            let morph_target_buffer_info = vk::DescriptorBufferInfo::builder()
+                .buffer(asset.morph_target_buffer.buffer())
+                .offset(0)
+                .range(vk::WHOLE_SIZE)
+                .build();
+            let morph_target_buffer_infos = [morph_target_buffer_info];
+
+            let morph_target_descriptor_write = vk::WriteDescriptorSet::builder()
+                .dst_set(*descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&morph_target_buffer_infos)
+                .build();
+
+            let joint_buffer_info = vk::DescriptorBufferInfo::builder()
+                .buffer(asset.joint_buffer.buffer())
+                .offset(0)
+                .range(vk::WHOLE_SIZE)
+                .build();
+            let joint_buffer_infos = [joint_buffer_info];
+
+            let joint_descriptor_write = vk::WriteDescriptorSet::builder()
+                .dst_set(*descriptor_set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&joint_buffer_infos)
+                .build();
+
+            let material_buffer_info = vk::DescriptorBufferInfo::builder()
+                .buffer(asset.materials.buffer.buffer())
+                .offset(0)
+                .range(vk::WHOLE_SIZE)
+                .build();
+            let material_buffer_infos = [material_buffer_info];
+
+            let material_descriptor_write = vk::WriteDescriptorSet::builder()
+                .dst_set(*descriptor_set)
+                .dst_binding(2)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&material_buffer_infos)
+                .build();
+
+            let descriptor_writes = [
+                morph_target_descriptor_write,
+                joint_descriptor_write,
+                material_descriptor_write,
+            ];
+
+            unsafe {
+                context
+                    .logical_device()
+                    .logical_device()
+                    .update_descriptor_sets(&descriptor_writes, &[])
+            }
+        }
+    }
 }
 
 pub struct PbrRenderer {
     command_buffer: vk::CommandBuffer,
+    pipelines: HashMap<PbrPipelineVariant, vk::Pipeline>,
     pipeline_layout: vk::PipelineLayout,
-    dynamic_alignment: u64,
     descriptor_set: vk::DescriptorSet,
+    asset_descriptor_sets: Vec<vk::DescriptorSet>,
+    instance_buffer: vk::Buffer,
 }
 
 impl PbrRenderer {
@@ -536,124 +1227,173 @@ impl PbrRenderer {
         command_buffer: vk::CommandBuffer,
         pipeline: &PbrPipeline,
         pipeline_data: &PbrPipelineData,
+        frame: usize,
     ) -> Self {
         Self {
             command_buffer,
-            pipeline_layout: pipeline.pipeline.layout(),
-            dynamic_alignment: pipeline_data.dynamic_alignment,
-            descriptor_set: pipeline_data.descriptor_set,
+            pipelines: pipeline
+                .pipelines
+                .iter()
+                .map(|(variant, pipeline)| (*variant, pipeline.pipeline()))
+                .collect(),
+            pipeline_layout: pipeline.layout(),
+            descriptor_set: pipeline_data.descriptor_sets[frame],
+            asset_descriptor_sets: pipeline_data.asset_descriptor_sets.clone(),
+            instance_buffer: pipeline_data.instance_buffers[frame].buffer(),
         }
     }
 
-    pub fn draw_asset(&self, device: &ash::Device, asset: &GltfAsset) {
-        let offsets = [0];
-        let vertex_buffers = [asset.buffers.vertex_buffer.buffer()];
+    // Only meshes with visible instances are drawn, using the instance counts from culling
+    pub fn draw_asset(
+        &self,
+        device: &ash::Device,
+        asset_index: usize,
+        asset: &GltfAsset,
+        visible_instances: &[VisibleInstances],
+    ) {
+        if visible_instances.iter().all(VisibleInstances::is_empty) {
+            return;
+        }
 
         unsafe {
-            device.cmd_bind_vertex_buffers(self.command_buffer, 0, &vertex_buffers, &offsets);
-            device.cmd_bind_index_buffer(
+            device.cmd_bind_vertex_buffers(
                 self.command_buffer,
-                asset
-                    .buffers
-                    .index_buffer
-                    .as_ref()
-                    .expect("Failed to get index buffer!")
-                    .buffer(),
                 0,
-                vk::IndexType::UINT32,
+                &[asset.buffers.vertex_buffer.buffer()],
+                &[0],
+            );
+            if let Some(index_buffer) = asset.buffers.index_buffer.as_ref() {
+                device.cmd_bind_index_buffer(
+                    self.command_buffer,
+                    index_buffer.buffer(),
+                    0,
+                    vk::IndexType::UINT32,
+                );
+            }
+            device.cmd_bind_descriptor_sets(
+                self.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_set, self.asset_descriptor_sets[asset_index]],
+                &[],
             );
         }
 
         asset.walk(|node_index, graph| {
             if let Some(mesh) = graph[node_index].mesh.as_ref() {
-                unsafe {
-                    device.cmd_bind_descriptor_sets(
-                        self.command_buffer,
-                        vk::PipelineBindPoint::GRAPHICS,
-                        self.pipeline_layout,
-                        0,
-                        &[self.descriptor_set],
-                        &[(mesh.mesh_id as u64 * self.dynamic_alignment) as _],
-                    );
+                let visible_instances = visible_instances[mesh.mesh_id];
+                if visible_instances.is_empty() {
+                    return;
                 }
 
-                for primitive in mesh.primitives.iter() {
-                    let material = Self::create_material(&asset, &primitive);
-                    unsafe {
-                        device.cmd_push_constants(
-                            self.command_buffer,
-                            self.pipeline_layout,
-                            vk::ShaderStageFlags::ALL_GRAPHICS,
-                            0,
-                            byte_slice_from(&material),
-                        );
+                let joint_offset = graph[node_index]
+                    .skin
+                    .as_ref()
+                    .map_or(-1, |skin| asset.joint_offsets[skin.index] as i32);
+
+                // The visible instances of the mesh are packed at the start of its range,
+                // with the mirrored instances after the others
+                let instance_groups = [
+                    (
+                        vk::FrontFace::COUNTER_CLOCKWISE,
+                        0,
+                        visible_instances.counter_clockwise,
+                    ),
+                    (
+                        vk::FrontFace::CLOCKWISE,
+                        visible_instances.counter_clockwise,
+                        visible_instances.clockwise,
+                    ),
+                ];
+                for (front_face, first_instance, instance_count) in instance_groups.iter().copied()
+                {
+                    if instance_count == 0 {
+                        continue;
+                    }
 
-                        device.cmd_draw_indexed(
+                    unsafe {
+                        device.cmd_bind_vertex_buffers(
                             self.command_buffer,
-                            primitive.number_of_indices,
                             1,
-                            primitive.first_index,
-                            0,
-                            0,
+                            &[self.instance_buffer],
+                            &[((mesh.first_instance + first_instance as usize)
+                                * mem::size_of::<ShaderInstance>())
+                                as _],
                         );
                     }
+
+                    for primitive in mesh.primitives.iter() {
+                        let primitive_block = PushConstantBlockPrimitive {
+                            first_vertex: primitive.first_vertex as i32,
+                            morph_target_offset: primitive.morph_target_offset as i32,
+                            number_of_morph_targets: primitive.number_of_morph_targets as i32,
+                            joint_offset,
+                        };
+                        // Primitives without a material use the default material stored last
+                        let material = PushConstantBlockMaterial {
+                            material_index: primitive
+                                .material_index
+                                .unwrap_or_else(|| asset.gltf.materials().len())
+                                as i32,
+                            texture_offset: asset.first_texture as i32,
+                        };
+
+                        let variant = PbrPipelineVariant {
+                            topology: primitive.topology,
+                            front_face,
+                            cull_mode: if primitive.is_double_sided {
+                                vk::CullModeFlags::NONE
+                            } else {
+                                vk::CullModeFlags::BACK
+                            },
+                        };
+
+                        unsafe {
+                            device.cmd_bind_pipeline(
+                                self.command_buffer,
+                                vk::PipelineBindPoint::GRAPHICS,
+                                self.pipelines[&variant],
+                            );
+
+                            device.cmd_push_constants(
+                                self.command_buffer,
+                                self.pipeline_layout,
+                                vk::ShaderStageFlags::VERTEX,
+                                0,
+                                byte_slice_from(&primitive_block),
+                            );
+
+                            device.cmd_push_constants(
+                                self.command_buffer,
+                                self.pipeline_layout,
+                                vk::ShaderStageFlags::FRAGMENT,
+                                MATERIAL_PUSH_CONSTANT_OFFSET,
+                                byte_slice_from(&material),
+                            );
+
+                            if primitive.is_indexed {
+                                device.cmd_draw_indexed(
+                                    self.command_buffer,
+                                    primitive.number_of_indices,
+                                    instance_count,
+                                    primitive.first_index,
+                                    0,
+                                    0,
+                                );
+                            } else {
+                                device.cmd_draw(
+                                    self.command_buffer,
+                                    primitive.number_of_vertices,
+                                    instance_count,
+                                    primitive.first_vertex,
+                                    0,
+                                );
+                            }
+                        }
+                    }
                 }
             }
         });
     }
-
-    fn create_material(asset: &GltfAsset, primitive: &Primitive) -> PushConstantBlockMaterial {
-        let mut material = PushConstantBlockMaterial {
-            base_color_factor: glm::vec4(0.0, 0.0, 0.0, 1.0),
-            emissive_factor: glm::Vec3::identity(),
-            color_texture_set: -1,
-            metallic_roughness_texture_set: -1,
-            normal_texture_set: -1,
-            occlusion_texture_set: -1,
-            emissive_texture_set: -1,
-            metallic_factor: 0.0,
-            roughness_factor: 0.0,
-            alpha_mask: gltf::material::AlphaMode::Opaque as i32,
-            alpha_mask_cutoff: 0.0,
-        };
-
-        if let Some(material_index) = primitive.material_index {
-            let primitive_material = asset
-                .gltf
-                .materials()
-                .nth(material_index)
-                .expect("Failed to retrieve material!");
-            let pbr = primitive_material.pbr_metallic_roughness();
-
-            material.base_color_factor = glm::Vec4::from(pbr.base_color_factor());
-            material.metallic_factor = pbr.metallic_factor();
-            material.roughness_factor = pbr.roughness_factor();
-            material.emissive_factor = glm::Vec3::from(primitive_material.emissive_factor());
-            material.alpha_mask_cutoff = primitive_material.alpha_cutoff();
-            material.alpha_mask = primitive_material.alpha_mode() as i32;
-
-            if let Some(base_color_texture) = pbr.base_color_texture() {
-                material.color_texture_set = base_color_texture.texture().index() as i32;
-            }
-
-            if let Some(metallic_roughness_texture) = pbr.metallic_roughness_texture() {
-                material.metallic_roughness_texture_set =
-                    metallic_roughness_texture.texture().index() as i32;
-            }
-
-            if let Some(normal_texture) = primitive_material.normal_texture() {
-                material.normal_texture_set = normal_texture.texture().index() as i32;
-            }
-
-            if let Some(occlusion_texture) = primitive_material.occlusion_texture() {
-                material.occlusion_texture_set = occlusion_texture.texture().index() as i32;
-            }
-
-            if let Some(emissive_texture) = primitive_material.emissive_texture() {
-                material.emissive_texture_set = emissive_texture.texture().index() as i32;
-            }
-        }
-
-        material
-    }
 }
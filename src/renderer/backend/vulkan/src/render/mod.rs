@@ -1,6 +1,9 @@
 pub use self::{
-    framebuffer::Framebuffer, pipeline::GraphicsPipeline, renderer::Renderer,
-    renderpass::RenderPass, vulkan_swapchain::VulkanSwapchain,
+    framebuffer::Framebuffer,
+    pipeline::GraphicsPipeline,
+    renderer::{AssetInstance, Renderer},
+    renderpass::RenderPass,
+    vulkan_swapchain::VulkanSwapchain,
 };
 
 pub mod environment;
@@ -7,8 +7,8 @@ use std::sync::Arc;
 
 pub struct GraphicsPipeline {
     pipeline: vk::Pipeline,
-    pipeline_layout: PipelineLayout,
-    descriptor_set_layout: DescriptorSetLayout,
+    pipeline_layout: Arc<PipelineLayout>,
+    descriptor_set_layout: Arc<DescriptorSetLayout>,
     context: Arc<VulkanContext>,
 }
 
@@ -18,6 +18,21 @@ impl GraphicsPipeline {
         create_info: vk::GraphicsPipelineCreateInfo,
         pipeline_layout: PipelineLayout,
         descriptor_set_layout: DescriptorSetLayout,
+    ) -> Self {
+        Self::with_shared_layout(
+            context,
+            create_info,
+            Arc::new(pipeline_layout),
+            Arc::new(descriptor_set_layout),
+        )
+    }
+
+    // Pipelines that only differ in fixed function state can share one layout
+    pub fn with_shared_layout(
+        context: Arc<VulkanContext>,
+        create_info: vk::GraphicsPipelineCreateInfo,
+        pipeline_layout: Arc<PipelineLayout>,
+        descriptor_set_layout: Arc<DescriptorSetLayout>,
     ) -> Self {
         let pipeline_create_info_arr = [create_info];
         let pipeline = unsafe {
@@ -1,8 +1,13 @@
 use crate::{
     core::VulkanContext,
-    model::{gltf::GltfAsset, ModelBuffers},
+    model::{
+        gltf::{GltfAsset, GltfAssetData, GltfImageData, GltfMaterialData},
+        loader::AssetLoader,
+        watcher::FileWatcher,
+        ModelBuffers,
+    },
     pipelines::{
-        pbr::{PbrPipeline, PbrPipelineData, PbrRenderer},
+        pbr::{PbrPipeline, PbrPipelineData, PbrRenderer, VisibleInstances, MAX_TEXTURES},
         skybox::{SkyboxPipeline, SkyboxPipelineData, SkyboxRenderer, VERTICES},
     },
     render::{
@@ -12,13 +17,22 @@ use crate::{
     },
     resource::{
         texture::{Cubemap, CubemapFaces},
-        CommandPool,
+        AssetCache, CommandPool, Handle, SamplerCache,
     },
-    sync::SynchronizationSet,
+    sync::{SynchronizationSet, SynchronizationSetConstants},
 };
 use ash::{version::DeviceV1_0, vk};
+use dragonglass_core::AssetCacheStats;
 use nalgebra_glm as glm;
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
+
+// An entity's copy of a loaded asset, drawn through the instance buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssetInstance {
+    pub asset_index: usize,
+    // The position of the entity among the instances of the asset
+    pub instance_index: usize,
+}
 
 pub struct Renderer {
     pub context: Arc<VulkanContext>,
@@ -27,12 +41,20 @@ pub struct Renderer {
     pub current_frame: usize,
     pub command_pool: CommandPool,
     pub transient_command_pool: CommandPool,
+    pub sampler_cache: SamplerCache,
+    pub image_cache: AssetCache<GltfImageData>,
+    pub material_cache: AssetCache<GltfMaterialData>,
+    pub environment_cache: AssetCache<Cubemap>,
     pub assets: Vec<GltfAsset>,
+    pub instances: Vec<Option<AssetInstance>>,
+    pub asset_loader: AssetLoader,
+    pub file_watcher: FileWatcher,
     pub pbr_pipeline: Option<PbrPipeline>,
     pub pbr_pipeline_data: Option<PbrPipelineData>,
     pub skybox_pipeline: Option<SkyboxPipeline>,
     pub skybox_pipeline_data: Option<SkyboxPipelineData>,
-    pub cubemap: Option<Cubemap>,
+    pub skybox_faces: CubemapFaces,
+    pub cubemap: Option<Arc<Cubemap>>,
     pub irradiance_map: Option<IrradianceMap>,
     pub prefilter_map: Option<PrefilterMap>,
     pub brdflut: Option<Brdflut>,
@@ -47,7 +69,11 @@ impl Renderer {
         let synchronization_set =
             SynchronizationSet::new(context.clone()).expect("Failed to create sync objects");
 
-        let command_pool = CommandPool::new(context.clone(), vk::CommandPoolCreateFlags::empty());
+        // Command buffers are recorded again every frame
+        let command_pool = CommandPool::new(
+            context.clone(),
+            vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        );
 
         let transient_command_pool =
             CommandPool::new(context.clone(), vk::CommandPoolCreateFlags::TRANSIENT);
@@ -63,6 +89,8 @@ impl Renderer {
             &command_pool,
         ));
 
+        let sampler_cache = SamplerCache::new(context.clone());
+
         let mut renderer = Renderer {
             context,
             synchronization_set,
@@ -70,11 +98,26 @@ impl Renderer {
             vulkan_swapchain,
             command_pool,
             transient_command_pool,
+            sampler_cache,
+            image_cache: AssetCache::default(),
+            material_cache: AssetCache::default(),
+            environment_cache: AssetCache::default(),
             assets: Vec::new(),
+            instances: Vec::new(),
+            asset_loader: AssetLoader::default(),
+            file_watcher: FileWatcher::default(),
             pbr_pipeline: None,
             pbr_pipeline_data: None,
             skybox_pipeline: None,
             skybox_pipeline_data: None,
+            skybox_faces: CubemapFaces {
+                left: "examples/assets/skyboxes/bluemountains/left.jpg".to_string(),
+                right: "examples/assets/skyboxes/bluemountains/right.jpg".to_string(),
+                top: "examples/assets/skyboxes/bluemountains/top.jpg".to_string(),
+                bottom: "examples/assets/skyboxes/bluemountains/bottom.jpg".to_string(),
+                front: "examples/assets/skyboxes/bluemountains/front.jpg".to_string(),
+                back: "examples/assets/skyboxes/bluemountains/back.jpg".to_string(),
+            },
             cubemap: None,
             irradiance_map: None,
             prefilter_map: None,
@@ -102,26 +145,16 @@ impl Renderer {
             println!("Failed to recompile shaders!");
         }
 
+        // The pipeline data may still be used by frames in flight
+        self.context.logical_device().wait_idle();
         self.pbr_pipeline = None;
         self.pbr_pipeline_data = None;
 
         let pbr_pipeline = PbrPipeline::new(self);
-
-        let textures = self
-            .assets
-            .iter()
-            .flat_map(|asset| &asset.textures)
-            .collect::<Vec<_>>();
-
-        let number_of_meshes = self.assets.iter().fold(0, |total_meshes, asset| {
-            total_meshes + asset.number_of_meshes
-        });
-
-        let pbr_pipeline_data = PbrPipelineData::new(&self, number_of_meshes, &textures);
+        let pbr_pipeline_data = PbrPipelineData::new(&self, &self.assets);
         self.pbr_pipeline = Some(pbr_pipeline);
         self.pbr_pipeline_data = Some(pbr_pipeline_data);
 
-        self.record_command_buffers();
         println!("Reloaded pbr shaders.");
     }
 
@@ -144,8 +177,6 @@ impl Renderer {
 
         self.pbr_pipeline = Some(pbr_pipeline);
         self.skybox_pipeline = Some(skybox_pipeline);
-
-        self.record_command_buffers();
     }
 
     pub fn load_environment(&mut self, cubemap: &Cubemap) {
@@ -171,65 +202,215 @@ impl Renderer {
         self.prefilter_map = Some(prefilter_map);
     }
 
-    pub fn load_assets(&mut self, asset_names: &[String]) {
-        let faces = CubemapFaces {
-            left: "examples/assets/skyboxes/bluemountains/left.jpg".to_string(),
-            right: "examples/assets/skyboxes/bluemountains/right.jpg".to_string(),
-            top: "examples/assets/skyboxes/bluemountains/top.jpg".to_string(),
-            bottom: "examples/assets/skyboxes/bluemountains/bottom.jpg".to_string(),
-            front: "examples/assets/skyboxes/bluemountains/front.jpg".to_string(),
-            back: "examples/assets/skyboxes/bluemountains/back.jpg".to_string(),
-        };
-
-        let cubemap = Cubemap::new(self.context.clone(), &self.transient_command_pool, &faces);
+    pub fn load_skybox(&mut self) {
+        let context = self.context.clone();
+        let command_pool = &self.transient_command_pool;
+        let faces = &self.skybox_faces;
+        let cubemap = self
+            .environment_cache
+            .get_or_insert_with(&Handle::from_key(faces), || {
+                Cubemap::new(context, command_pool, faces)
+            });
 
         self.load_environment(&cubemap);
 
-        let mut assets = Vec::new();
-        for asset_name in asset_names.iter() {
-            assets.push(GltfAsset::new(&self, asset_name));
-        }
+        let skybox_pipeline_data = SkyboxPipelineData::new(&self, &cubemap);
+        self.skybox_pipeline_data = Some(skybox_pipeline_data);
+        self.cubemap = Some(cubemap);
+        self.environment_cache.release_unused();
+    }
+
+    // Loads the skybox faces from disk again and rebuilds the environment maps
+    pub fn reload_skybox(&mut self) {
+        self.context.logical_device().wait_idle();
+        self.pbr_pipeline_data = None;
+        self.environment_cache
+            .remove(&Handle::from_key(&self.skybox_faces));
+        self.load_skybox();
+        self.pbr_pipeline_data = Some(PbrPipelineData::new(&self, &self.assets));
+    }
 
-        let number_of_meshes = assets.iter().fold(0, |total_meshes, asset| {
-            total_meshes + asset.number_of_meshes
-        });
+    // The files behind the skybox and every loaded asset
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        self.skybox_faces
+            .ordered_faces()
+            .map(PathBuf::from)
+            .chain(
+                self.assets
+                    .iter()
+                    .flat_map(|asset| asset.files.iter().cloned()),
+            )
+            .collect()
+    }
 
-        let textures = assets
+    // Uploads the newly loaded assets and frees the ones no longer named.
+    // Entities coming and going only change the instances, unless they outgrow the buffers.
+    pub fn load_assets(&mut self, asset_names: &[String], loaded_assets: Vec<GltfAssetData>) {
+        // Different names for the same file resolve to the same handle
+        let asset_handles = asset_names
             .iter()
-            .flat_map(|asset| &asset.textures)
+            .map(|asset_name| GltfAsset::handle(asset_name))
             .collect::<Vec<_>>();
 
-        self.pbr_pipeline_data = Some(PbrPipelineData::new(&self, number_of_meshes, &textures));
+        let is_asset_set_unchanged = self
+            .assets
+            .iter()
+            .all(|asset| asset_handles.contains(&asset.handle))
+            && !loaded_assets
+                .iter()
+                .any(|data| asset_handles.contains(&data.handle));
+        if let Some(pbr_data) = self
+            .pbr_pipeline_data
+            .as_ref()
+            .filter(|_| is_asset_set_unchanged)
+        {
+            let instances = Self::assign_instances(&mut self.assets, &asset_handles);
+            let number_of_instance_transforms = self
+                .assets
+                .iter()
+                .map(|asset| asset.number_of_instance_transforms())
+                .sum::<usize>();
+            let has_room = number_of_instance_transforms <= pbr_data.instance_capacity
+                && self
+                    .assets
+                    .iter()
+                    .all(|asset| asset.number_of_instances <= asset.instance_capacity);
+            if has_room {
+                self.instances = instances;
+                return;
+            }
+        }
 
-        let skybox_pipeline_data = SkyboxPipelineData::new(&self, &cubemap);
-        self.skybox_pipeline_data = Some(skybox_pipeline_data);
+        // Frames in flight may still be using the current resources
+        self.context.logical_device().wait_idle();
+        self.pbr_pipeline_data = None;
+
+        let mut assets = std::mem::take(&mut self.assets);
+        assets.retain(|asset| asset_handles.contains(&asset.handle));
+
+        // Reloaded assets replace the asset that was loaded from the same file
+        for data in loaded_assets {
+            if !asset_handles.contains(&data.handle) {
+                continue;
+            }
+
+            // Every texture of every asset needs a slot in the pbr descriptor set
+            let number_of_textures = assets
+                .iter()
+                .filter(|asset| asset.handle != data.handle)
+                .map(|asset| asset.textures.len())
+                .sum::<usize>()
+                + data.gltf.textures().count();
+            if number_of_textures > MAX_TEXTURES as usize {
+                log::warn!(
+                    "Failed to load asset '{}': the loaded assets would use {} textures, but only {} are supported",
+                    data.name,
+                    number_of_textures,
+                    MAX_TEXTURES
+                );
+                self.asset_loader.failed.insert(data.name);
+                continue;
+            }
+
+            assets.retain(|asset| asset.handle != data.handle);
+            assets.push(GltfAsset::new(self, data));
+        }
+
+        // Images, materials and samplers are freed once no remaining asset uses them
+        self.image_cache.release_unused();
+        self.material_cache.release_unused();
+        self.sampler_cache.release_unused();
+
+        let instances = Self::assign_instances(&mut assets, &asset_handles);
+
+        // Textures are allocated across all assets, and every entity gets its own joint matrices
+        let mut first_texture = 0;
+        for asset in assets.iter_mut() {
+            asset.first_texture = first_texture;
+            first_texture += asset.textures.len();
+            asset.allocate_joint_buffer(self.context.clone());
+        }
+
+        self.pbr_pipeline_data = Some(PbrPipelineData::new(&self, &assets));
         self.assets = assets;
-        self.cubemap = Some(cubemap);
+        self.instances = instances;
+    }
+
+    // Entities that share an asset file share the loaded asset,
+    // and entities whose asset is still loading don't have an instance yet.
+    // Each mesh gets a range of the instance buffer large enough for all its instances.
+    fn assign_instances(
+        assets: &mut [GltfAsset],
+        asset_handles: &[Handle<GltfAsset>],
+    ) -> Vec<Option<AssetInstance>> {
+        assets
+            .iter_mut()
+            .for_each(|asset| asset.number_of_instances = 0);
+
+        let mut instances = Vec::new();
+        for asset_handle in asset_handles.iter() {
+            let asset_index = assets
+                .iter()
+                .position(|asset| &asset.handle == asset_handle);
+            let instance = asset_index.map(|asset_index| {
+                let asset = &mut assets[asset_index];
+                asset.number_of_instances += 1;
+                AssetInstance {
+                    asset_index,
+                    instance_index: asset.number_of_instances - 1,
+                }
+            });
+            instances.push(instance);
+        }
+
+        let mut first_instance = 0;
+        for asset in assets.iter_mut() {
+            first_instance = asset.assign_instance_ranges(first_instance);
+        }
+        instances
+    }
+
+    pub fn is_asset_loaded(&self, asset_name: &str) -> bool {
+        let handle = GltfAsset::handle(asset_name);
+        self.assets.iter().any(|asset| asset.handle == handle)
+    }
+
+    pub fn asset_cache_stats(&self) -> AssetCacheStats {
+        AssetCacheStats {
+            assets: self.assets.len(),
+            textures: self.image_cache.len(),
+            materials: self.material_cache.len(),
+            environments: self.environment_cache.len(),
+            samplers: self.sampler_cache.len(),
+        }
     }
 
     pub fn allocate_command_buffers(&mut self) {
-        // Allocate one command buffer per swapchain image
-        let number_of_framebuffers = self.vulkan_swapchain().framebuffers.len();
+        // Allocate one command buffer per frame in flight,
+        // which is only recorded again once its frame has finished
         self.command_pool
-            .allocate_command_buffers(number_of_framebuffers as _);
+            .allocate_command_buffers(SynchronizationSet::MAX_FRAMES_IN_FLIGHT as _);
     }
 
-    pub fn record_command_buffers(&self) {
-        // Create a single render pass per swapchain image that will draw each mesh
-        self.command_pool
-            .command_buffers()
-            .iter()
-            .enumerate()
-            .for_each(|(index, buffer)| {
-                let command_buffer = *buffer;
-                let framebuffer = self.vulkan_swapchain().framebuffers[index].framebuffer();
-                self.draw(framebuffer, command_buffer);
-            });
+    // Records the current frame's command buffer with the meshes that survived culling
+    pub fn record_command_buffer(
+        &self,
+        image_index: usize,
+        visible_instances: &[Vec<VisibleInstances>],
+    ) {
+        let command_buffer = self.command_pool.command_buffers()[self.current_frame];
+        let framebuffer = self.vulkan_swapchain().framebuffers[image_index].framebuffer();
+        self.draw(framebuffer, command_buffer, visible_instances);
     }
 
-    pub fn draw(&self, framebuffer: vk::Framebuffer, command_buffer: vk::CommandBuffer) {
+    pub fn draw(
+        &self,
+        framebuffer: vk::Framebuffer,
+        command_buffer: vk::CommandBuffer,
+        visible_instances: &[Vec<VisibleInstances>],
+    ) {
         let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
-            .flags(vk::CommandBufferUsageFlags::SIMULTANEOUS_USE)
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
             .build();
         unsafe {
             self.context
@@ -275,7 +456,7 @@ impl Renderer {
         }
 
         self.render_skybox(command_buffer);
-        self.render_assets(command_buffer);
+        self.render_assets(command_buffer, visible_instances);
 
         unsafe {
             self.context
@@ -291,7 +472,11 @@ impl Renderer {
         }
     }
 
-    pub fn render_assets(&self, command_buffer: vk::CommandBuffer) {
+    pub fn render_assets(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        visible_instances: &[Vec<VisibleInstances>],
+    ) {
         let device = &self.context.logical_device().logical_device();
 
         let pbr_pipeline = self
@@ -299,20 +484,27 @@ impl Renderer {
             .as_ref()
             .expect("Failed to get pbr pipeline!");
 
-        pbr_pipeline.bind(device, command_buffer);
-
         let pbr_pipeline_data = self
             .pbr_pipeline_data
             .as_ref()
             .expect("Failed to get pbr pipeline data!");
 
-        let pbr_renderer = PbrRenderer::new(command_buffer, &pbr_pipeline, &pbr_pipeline_data);
+        let pbr_renderer = PbrRenderer::new(
+            command_buffer,
+            &pbr_pipeline,
+            &pbr_pipeline_data,
+            self.current_frame,
+        );
 
         self.update_viewport(command_buffer);
 
         self.assets
             .iter()
-            .for_each(|asset| pbr_renderer.draw_asset(device, &asset));
+            .enumerate()
+            .zip(visible_instances.iter())
+            .for_each(|((index, asset), visible_instances)| {
+                pbr_renderer.draw_asset(device, index, &asset, visible_instances)
+            });
     }
 
     pub fn render_skybox(&self, command_buffer: vk::CommandBuffer) {
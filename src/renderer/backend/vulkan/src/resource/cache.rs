@@ -0,0 +1,132 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    sync::Arc,
+};
+
+// A typed key for an asset in an asset cache.
+// The handle keeps the bytes its key hashes, so keys that only share a hash stay distinct.
+pub struct Handle<T> {
+    id: u64,
+    key: Arc<[u8]>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    // Equal keys always produce the same handle
+    pub fn from_key<K: Hash + ?Sized>(key: &K) -> Self {
+        let mut writer = KeyWriter::default();
+        key.hash(&mut writer);
+        let mut hasher = DefaultHasher::new();
+        hasher.write(&writer.0);
+        Self {
+            id: hasher.finish(),
+            key: writer.0.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+// Records the bytes a key hashes instead of hashing them
+#[derive(Default)]
+struct KeyWriter(Vec<u8>);
+
+impl Hasher for KeyWriter {
+    fn finish(&self) -> u64 {
+        unreachable!("Keys are only written, never finished")
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            key: self.key.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && (Arc::ptr_eq(&self.key, &other.key) || self.key == other.key)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Handle({:#018x})", self.id)
+    }
+}
+
+// Shares assets between everything that uses them.
+// An asset is freed once the cache holds the only reference to it.
+pub struct AssetCache<T> {
+    assets: HashMap<Handle<T>, Arc<T>>,
+}
+
+impl<T> Default for AssetCache<T> {
+    fn default() -> Self {
+        Self {
+            assets: HashMap::new(),
+        }
+    }
+}
+
+impl<T> AssetCache<T> {
+    pub fn get_or_insert_with<F>(&mut self, handle: &Handle<T>, create: F) -> Arc<T>
+    where
+        F: FnOnce() -> T,
+    {
+        self.assets
+            .entry(handle.clone())
+            .or_insert_with(|| Arc::new(create()))
+            .clone()
+    }
+
+    pub fn get(&self, handle: &Handle<T>) -> Option<Arc<T>> {
+        self.assets.get(handle).cloned()
+    }
+
+    pub fn contains(&self, handle: &Handle<T>) -> bool {
+        self.assets.contains_key(handle)
+    }
+
+    // The asset stays alive for as long as anything else still refers to it
+    pub fn remove(&mut self, handle: &Handle<T>) -> Option<Arc<T>> {
+        self.assets.remove(handle)
+    }
+
+    // Returns the number of assets that were freed
+    pub fn release_unused(&mut self) -> usize {
+        let number_of_assets = self.assets.len();
+        self.assets.retain(|_, asset| Arc::strong_count(asset) > 1);
+        number_of_assets - self.assets.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+}
@@ -1,17 +1,19 @@
 pub use self::{
     buffer::Buffer,
+    cache::{AssetCache, Handle},
     command_pool::CommandPool,
     descriptor_pool::DescriptorPool,
     descriptor_set_layout::DescriptorSetLayout,
     dummy::DummyImage,
     image_view::ImageView,
     pipeline_layout::PipelineLayout,
-    sampler::Sampler,
+    sampler::{Sampler, SamplerCache},
     shader::Shader,
     texture::{Texture, TextureDescription},
 };
 
 pub mod buffer;
+pub mod cache;
 pub mod command_pool;
 pub mod descriptor_pool;
 pub mod descriptor_set_layout;
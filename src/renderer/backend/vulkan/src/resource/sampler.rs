@@ -1,6 +1,6 @@
 use crate::core::VulkanContext;
 use ash::{version::DeviceV1_0, vk};
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 // Add snafu errors
 
@@ -37,3 +37,85 @@ impl Drop for Sampler {
         };
     }
 }
+
+// The hashable fields of a sampler create info
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct SamplerKey {
+    mag_filter: vk::Filter,
+    min_filter: vk::Filter,
+    mipmap_mode: vk::SamplerMipmapMode,
+    address_mode_u: vk::SamplerAddressMode,
+    address_mode_v: vk::SamplerAddressMode,
+    address_mode_w: vk::SamplerAddressMode,
+    mip_lod_bias: u32,
+    anisotropy_enable: vk::Bool32,
+    max_anisotropy: u32,
+    compare_enable: vk::Bool32,
+    compare_op: vk::CompareOp,
+    min_lod: u32,
+    max_lod: u32,
+    border_color: vk::BorderColor,
+    unnormalized_coordinates: vk::Bool32,
+}
+
+impl From<&vk::SamplerCreateInfo> for SamplerKey {
+    fn from(create_info: &vk::SamplerCreateInfo) -> Self {
+        Self {
+            mag_filter: create_info.mag_filter,
+            min_filter: create_info.min_filter,
+            mipmap_mode: create_info.mipmap_mode,
+            address_mode_u: create_info.address_mode_u,
+            address_mode_v: create_info.address_mode_v,
+            address_mode_w: create_info.address_mode_w,
+            mip_lod_bias: create_info.mip_lod_bias.to_bits(),
+            anisotropy_enable: create_info.anisotropy_enable,
+            max_anisotropy: create_info.max_anisotropy.to_bits(),
+            compare_enable: create_info.compare_enable,
+            compare_op: create_info.compare_op,
+            min_lod: create_info.min_lod.to_bits(),
+            max_lod: create_info.max_lod.to_bits(),
+            border_color: create_info.border_color,
+            unnormalized_coordinates: create_info.unnormalized_coordinates,
+        }
+    }
+}
+
+// Shares samplers between every texture created with the same settings.
+// A sampler is destroyed once the cache holds the only reference to it.
+pub struct SamplerCache {
+    samplers: HashMap<SamplerKey, Arc<Sampler>>,
+    context: Arc<VulkanContext>,
+}
+
+impl SamplerCache {
+    pub fn new(context: Arc<VulkanContext>) -> Self {
+        Self {
+            samplers: HashMap::new(),
+            context,
+        }
+    }
+
+    pub fn sampler(&mut self, create_info: vk::SamplerCreateInfo) -> Arc<Sampler> {
+        let context = self.context.clone();
+        self.samplers
+            .entry(SamplerKey::from(&create_info))
+            .or_insert_with(|| Arc::new(Sampler::new(context, create_info)))
+            .clone()
+    }
+
+    // Returns the number of samplers that were destroyed
+    pub fn release_unused(&mut self) -> usize {
+        let number_of_samplers = self.samplers.len();
+        self.samplers
+            .retain(|_, sampler| Arc::strong_count(sampler) > 1);
+        number_of_samplers - self.samplers.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+}
@@ -95,10 +95,14 @@ impl TextureDescription {
         match format {
             Format::R8 => vk::Format::R8_UNORM,
             Format::R8G8 => vk::Format::R8G8_UNORM,
-            Format::R8G8B8A8 => vk::Format::R8G8B8A8_UNORM,
-            Format::B8G8R8A8 => vk::Format::B8G8R8A8_UNORM,
             Format::R8G8B8 => vk::Format::R8G8B8_UNORM,
-            Format::B8G8R8 => vk::Format::B8G8R8_UNORM,
+            Format::R8G8B8A8 => vk::Format::R8G8B8A8_UNORM,
+            Format::R16 => vk::Format::R16_UNORM,
+            Format::R16G16 => vk::Format::R16G16_UNORM,
+            Format::R16G16B16 => vk::Format::R16G16B16_UNORM,
+            Format::R16G16B16A16 => vk::Format::R16G16B16A16_UNORM,
+            Format::R32G32B32FLOAT => vk::Format::R32G32B32_SFLOAT,
+            Format::R32G32B32A32FLOAT => vk::Format::R32G32B32A32_SFLOAT,
         }
     }
 }
@@ -379,6 +383,7 @@ impl Drop for Texture {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CubemapFaces {
     pub right: String,
     pub left: String,
@@ -389,7 +394,7 @@ pub struct CubemapFaces {
 }
 
 impl CubemapFaces {
-    fn ordered_faces(&self) -> impl Iterator<Item = String> {
+    pub fn ordered_faces(&self) -> impl Iterator<Item = String> {
         iter::once(self.right.to_string())
             .chain(iter::once(self.left.to_string()))
             .chain(iter::once(self.top.to_string()))
@@ -0,0 +1,180 @@
+use crate::{
+    model::gltf::AssetPose,
+    render::{AssetInstance, Renderer},
+};
+use dragonglass_core::{
+    bounds::BoundingBox,
+    camera::{ActiveCamera, Camera, CameraState, Projection, SceneCamera},
+    components::{AssetChild, Selected, Transform},
+    input::Input,
+    AppState,
+};
+use legion::prelude::*;
+use nalgebra_glm as glm;
+use std::collections::HashSet;
+use winit::VirtualKeyCode;
+
+// Cycles through the scene cameras with the C key and
+// drives the camera state from the active scene camera
+pub fn scene_camera_system() -> Box<dyn Schedulable> {
+    let mut can_cycle = true;
+    SystemBuilder::new("scene_camera")
+        .read_resource::<Renderer>()
+        .read_resource::<Input>()
+        .write_resource::<ActiveCamera>()
+        .write_resource::<CameraState>()
+        .read_component::<AssetPose>()
+        .with_query(<(Read<SceneCamera>, Read<Transform>, Read<AssetChild>)>::query())
+        .build(
+            move |_, world, (renderer, input, active_camera, camera_state), query| {
+                if input.is_key_pressed(VirtualKeyCode::C) && can_cycle {
+                    can_cycle = false;
+
+                    // Cameras of unloaded assets leave gaps in the indices
+                    let mut indices = query
+                        .iter(world)
+                        .map(|(scene_camera, _, _)| scene_camera.index)
+                        .collect::<Vec<_>>();
+                    indices.sort_unstable();
+                    active_camera.0 = match active_camera.0 {
+                        None => indices.first().copied(),
+                        Some(active_index) => {
+                            indices.iter().find(|index| **index > active_index).copied()
+                        }
+                    };
+
+                    // Return the projection to the app camera's default
+                    if active_camera.0.is_none() {
+                        camera_state.projection = Projection::default();
+                    }
+                }
+
+                if !input.is_key_pressed(VirtualKeyCode::C) {
+                    can_cycle = true;
+                }
+
+                let active_index = match active_camera.0 {
+                    Some(index) => index,
+                    None => return,
+                };
+
+                // The app camera takes over again when the active camera's asset is unloaded
+                if !query
+                    .iter(world)
+                    .any(|(scene_camera, _, _)| scene_camera.index == active_index)
+                {
+                    active_camera.0 = None;
+                    camera_state.projection = Projection::default();
+                    return;
+                }
+
+                for (scene_camera, transform, asset_child) in query.iter(world) {
+                    if scene_camera.index != active_index {
+                        continue;
+                    }
+
+                    // Cameras follow the pose of the entity they were spawned from
+                    let node_transform = match world.get_component::<AssetPose>(asset_child.0) {
+                        Some(pose) => pose.node_transform(scene_camera.node_index),
+                        None => renderer
+                            .assets
+                            .iter()
+                            .find(|asset| asset.name == scene_camera.asset_name)
+                            .and_then(|asset| {
+                                asset.rest_pose.node_transform(scene_camera.node_index)
+                            }),
+                    };
+                    let node_transform = match node_transform {
+                        Some(node_transform) => node_transform,
+                        None => continue,
+                    };
+                    let camera_transform =
+                        transform.translate * transform.rotate * transform.scale * node_transform;
+
+                    // The world is rendered with its y axis flipped
+                    let flip = glm::scaling(&glm::vec3(1.0, -1.0, 1.0));
+                    camera_state.view = flip * glm::inverse(&camera_transform) * flip;
+                    let position = camera_transform.column(3).xyz();
+                    camera_state.position = glm::vec3(position.x, -position.y, position.z);
+                    camera_state.projection = scene_camera.projection;
+                }
+            },
+        )
+}
+
+// Frames the selected assets, or every asset if none are selected, with the F key.
+// Everything is framed when new assets finish loading.
+pub fn camera_framing_system() -> Box<dyn Schedulable> {
+    let mut can_frame = true;
+    // The entities whose assets had finished loading when the camera last checked
+    let mut loaded_entities = HashSet::new();
+    SystemBuilder::new("camera_framing")
+        .read_resource::<Renderer>()
+        .read_resource::<Input>()
+        .read_resource::<AppState>()
+        .with_query(<(
+            Read<AssetInstance>,
+            TryRead<Transform>,
+            TryRead<Selected>,
+            TryRead<AssetPose>,
+        )>::query())
+        .with_query(<Write<Camera>>::query())
+        .build(
+            move |_, world, (renderer, input, app_state), (asset_query, camera_query)| {
+                let is_frame_key_pressed = input.is_key_pressed(VirtualKeyCode::F);
+                let frame_selection = is_frame_key_pressed && can_frame;
+                can_frame = !is_frame_key_pressed;
+
+                let mut entities = HashSet::new();
+                let instances = asset_query
+                    .iter_entities(world)
+                    .map(|(entity, (instance, transform, selected, pose))| {
+                        entities.insert(entity);
+                        let transform = transform.map_or_else(glm::Mat4::identity, |transform| {
+                            transform.translate * transform.rotate * transform.scale
+                        });
+                        // Animated entities are framed in their current pose
+                        let bounding_box = match pose {
+                            Some(pose) => pose.bounding_box,
+                            None => renderer
+                                .assets
+                                .get(instance.asset_index)
+                                .and_then(|asset| asset.rest_pose.bounding_box),
+                        };
+                        (bounding_box, transform, selected.is_some())
+                    })
+                    .collect::<Vec<_>>();
+                // Entities get an asset instance once their asset has finished loading
+                let has_new_content = !entities.is_subset(&loaded_entities);
+                loaded_entities = entities;
+                if !frame_selection && !has_new_content {
+                    return;
+                }
+
+                let has_selection =
+                    frame_selection && instances.iter().any(|(_, _, selected)| *selected);
+
+                // The world is rendered with its y axis flipped
+                let flip = glm::scaling(&glm::vec3(1.0, -1.0, 1.0));
+                let bounding_box = instances
+                    .iter()
+                    .filter(|(_, _, selected)| *selected || !has_selection)
+                    .filter_map(|(bounding_box, transform, _)| {
+                        bounding_box.map(|bounding_box| bounding_box.transform(&(flip * transform)))
+                    })
+                    .fold(None, |frame: Option<BoundingBox>, bounding_box| {
+                        Some(frame.map_or(bounding_box, |frame| frame.merge(&bounding_box)))
+                    });
+                let bounding_box = match bounding_box {
+                    Some(bounding_box) => bounding_box,
+                    None => return,
+                };
+
+                let aspect_ratio =
+                    app_state.window.width as f32 / app_state.window.height.max(1) as f32;
+                for mut camera in camera_query.iter(world) {
+                    camera.frame(&bounding_box, aspect_ratio);
+                }
+            },
+        )
+}
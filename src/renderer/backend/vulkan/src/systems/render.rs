@@ -1,35 +1,270 @@
 use crate::{
-    model::gltf::GltfAsset,
+    model::gltf::{AssetPose, GltfAsset},
     pipelines::{
-        pbr::{DynamicUniformBufferObject, UniformBufferObject},
+        pbr::{
+            ShaderInstance, ShaderLight, UniformBufferObject, VisibleInstances,
+            LIGHT_BUFFER_HEADER_SIZE, MAX_LIGHTS,
+        },
         skybox::UniformBufferObject as SkyboxUniformBufferObject,
     },
-    render::Renderer,
+    render::{AssetInstance, Renderer},
     sync::{SynchronizationSet, SynchronizationSetConstants},
 };
 use ash::vk;
 use dragonglass_core::{
-    camera::CameraState,
-    components::{AssetName, Transform},
+    animation::AnimationState,
+    bounds::Frustum,
+    camera::{CameraState, SceneCamera},
+    components::{AssetChild, AssetName, AssetNode, Light, Transform},
     input::Input,
-    AnimationState, AppState,
+    AppState, AssetCacheStats, AssetLoadingProgress, CullingStats, DeltaTime,
 };
 use legion::prelude::*;
 use nalgebra_glm as glm;
+use std::{collections::HashMap, path::PathBuf};
 use winit::VirtualKeyCode;
 
 pub fn prepare_renderer_system() -> Box<dyn Schedulable> {
     SystemBuilder::new("prepare_renderer")
+        .write_resource::<Renderer>()
+        .build(|_, _, renderer, _| {
+            // Assets are loaded by the asset loading system once their entities exist
+            renderer.load_skybox();
+            renderer.load_assets(&[], Vec::new());
+            renderer.allocate_command_buffers();
+        })
+}
+
+// Loads new assets in the background and frees the assets that are no longer used
+pub fn asset_loading_system() -> Box<dyn Schedulable> {
+    let mut last_entities: Vec<(Entity, String)> = Vec::new();
+    let mut spawned_entities: Vec<(Entity, String)> = Vec::new();
+    let mut number_of_cameras = 0;
+    SystemBuilder::new("asset_loading")
+        .write_resource::<Renderer>()
+        .write_resource::<AssetLoadingProgress>()
+        .write_resource::<AssetCacheStats>()
+        .with_query(<(Read<AssetName>, TryRead<Transform>)>::query())
+        .with_query(<Read<AssetChild>>::query())
+        .build(
+            move |commands, world, (renderer, progress, stats), (query, child_query)| {
+                let assets = query
+                    .iter_entities(world)
+                    .map(|(entity, (asset_name, transform))| {
+                        let asset_transform = transform
+                            .map_or_else(glm::Mat4::identity, |transform| {
+                                transform.translate * transform.rotate * transform.scale
+                            });
+                        (entity, asset_name.0.to_string(), asset_transform)
+                    })
+                    .collect::<Vec<_>>();
+                let entities = assets
+                    .iter()
+                    .map(|(entity, asset_name, _)| (*entity, asset_name.to_string()))
+                    .collect::<Vec<_>>();
+
+                // Assets are looked up by handle, so this only happens when entities change
+                if entities != last_entities {
+                    for (_, asset_name, _) in assets.iter() {
+                        if !renderer.is_asset_loaded(asset_name) {
+                            renderer.asset_loader.load(asset_name);
+                        }
+                    }
+                }
+                let loaded_assets = renderer.asset_loader.finished();
+
+                if entities != last_entities || !loaded_assets.is_empty() {
+                    let asset_names = assets
+                        .iter()
+                        .map(|(_, asset_name, _)| asset_name.to_string())
+                        .collect::<Vec<_>>();
+                    let reloaded = loaded_assets
+                        .iter()
+                        .filter(|data| {
+                            renderer
+                                .assets
+                                .iter()
+                                .any(|asset| asset.handle == data.handle)
+                        })
+                        .map(|data| data.handle.clone())
+                        .collect::<Vec<_>>();
+                    renderer.load_assets(&asset_names, loaded_assets);
+
+                    // Files are watched even if their asset failed to load, so that fixing them reloads it
+                    let watched_files = asset_names
+                        .iter()
+                        .map(PathBuf::from)
+                        .chain(renderer.source_files())
+                        .collect::<Vec<_>>();
+                    renderer.file_watcher.watch_files(watched_files);
+
+                    // Lights and cameras are removed along with the entity they were spawned from,
+                    // and spawned again from reloaded assets
+                    spawned_entities.retain(|spawned| entities.contains(spawned));
+                    if !reloaded.is_empty() {
+                        spawned_entities.retain(|(_, asset_name)| {
+                            !reloaded.contains(&GltfAsset::handle(asset_name))
+                        });
+                    }
+                    for (entity, asset_child) in child_query.iter_entities(world) {
+                        if !spawned_entities
+                            .iter()
+                            .any(|(parent, _)| *parent == asset_child.0)
+                        {
+                            commands.delete(entity);
+                        }
+                    }
+
+                    for (instance, (entity, asset_name, asset_transform)) in
+                        renderer.instances.iter().zip(assets.iter())
+                    {
+                        // The asset is still loading
+                        let instance = match instance {
+                            Some(instance) => instance,
+                            None => {
+                                commands.remove_component::<AssetInstance>(*entity);
+                                commands.remove_component::<AssetPose>(*entity);
+                                continue;
+                            }
+                        };
+                        commands.add_component(*entity, *instance);
+
+                        // Poses only match the asset they were evaluated for, so entities
+                        // with a reloaded or different asset are animated again
+                        let is_reloaded = reloaded.contains(&GltfAsset::handle(asset_name));
+                        if is_reloaded
+                            || !last_entities.contains(&(*entity, asset_name.to_string()))
+                        {
+                            commands.remove_component::<AssetPose>(*entity);
+                        }
+
+                        let spawned = (*entity, asset_name.to_string());
+                        if spawned_entities.contains(&spawned) {
+                            continue;
+                        }
+                        spawned_entities.push(spawned);
+
+                        // Lights and cameras in newly loaded assets become entities
+                        let asset = &renderer.assets[instance.asset_index];
+                        // Lights and cameras follow their node, so they keep the transform of the asset
+                        let lights = asset
+                            .lights
+                            .iter()
+                            .map(|(light, node_index)| {
+                                (
+                                    light.clone(),
+                                    decompose_transform(asset_transform),
+                                    AssetChild(*entity),
+                                    AssetNode(*node_index),
+                                )
+                            })
+                            .collect::<Vec<_>>();
+                        commands.insert((), lights);
+
+                        let cameras = asset
+                            .cameras
+                            .iter()
+                            .map(|camera| {
+                                let scene_camera = SceneCamera {
+                                    name: camera.name.clone(),
+                                    asset_name: asset.name.to_string(),
+                                    node_index: camera.node_index,
+                                    projection: camera.projection,
+                                    index: number_of_cameras,
+                                };
+                                number_of_cameras += 1;
+                                (
+                                    scene_camera,
+                                    decompose_transform(asset_transform),
+                                    AssetChild(*entity),
+                                )
+                            })
+                            .collect::<Vec<_>>();
+                        commands.insert((), cameras);
+                    }
+
+                    last_entities = entities;
+                }
+
+                let mut failed = renderer
+                    .asset_loader
+                    .failed
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                failed.sort();
+                **progress = AssetLoadingProgress {
+                    loading: renderer.asset_loader.pending.clone(),
+                    failed,
+                    loaded: renderer.assets.len(),
+                };
+                **stats = renderer.asset_cache_stats();
+            },
+        )
+}
+
+// Splits a global transform into the components of a transform
+fn decompose_transform(transform: &glm::Mat4) -> Transform {
+    let translation = transform.column(3).xyz();
+    let scale = glm::vec3(
+        transform.column(0).xyz().norm(),
+        transform.column(1).xyz().norm(),
+        transform.column(2).xyz().norm(),
+    );
+    let mut rotate = glm::Mat4::identity();
+    for column in 0..3 {
+        let axis = transform.column(column).xyz() / scale[column];
+        rotate.set_column(column, &glm::vec4(axis.x, axis.y, axis.z, 0.0));
+    }
+    Transform {
+        translate: glm::translation(&translation),
+        rotate,
+        scale: glm::scaling(&scale),
+    }
+}
+
+// Reloads assets and the skybox when their files change on disk.
+// Reloaded assets keep their entities, so transforms and animation states are preserved.
+pub fn file_reload_system() -> Box<dyn Schedulable> {
+    SystemBuilder::new("file_reload")
         .write_resource::<Renderer>()
         .with_query(<Read<AssetName>>::query())
-        .build(|_, mut world, renderer, query| {
-            let asset_names = query
-                .iter(&mut world)
-                .map(|asset_name| asset_name.0.to_string())
+        .build(move |_, world, renderer, query| {
+            let changed_files = renderer.file_watcher.changed_files();
+            if changed_files.is_empty() {
+                return;
+            }
+
+            let skybox_changed = renderer
+                .skybox_faces
+                .ordered_faces()
+                .any(|face| changed_files.contains(&PathBuf::from(face)));
+            if skybox_changed {
+                log::info!("Reloading skybox.");
+                renderer.reload_skybox();
+            }
+
+            let mut asset_names = renderer
+                .assets
+                .iter()
+                .filter(|asset| asset.files.iter().any(|file| changed_files.contains(file)))
+                .map(|asset| asset.name.to_string())
                 .collect::<Vec<_>>();
-            renderer.load_assets(&asset_names);
-            renderer.allocate_command_buffers();
-            renderer.record_command_buffers();
+
+            // Assets that aren't loaded only have their asset file watched
+            for asset_name in query.iter(world) {
+                let is_changed = changed_files.contains(&PathBuf::from(&asset_name.0));
+                if is_changed && !renderer.is_asset_loaded(&asset_name.0) {
+                    asset_names.push(asset_name.0.to_string());
+                }
+            }
+            asset_names.sort();
+            asset_names.dedup();
+
+            for asset_name in asset_names.iter() {
+                log::info!("Reloading asset '{}'.", asset_name);
+                renderer.asset_loader.reload(asset_name);
+            }
         })
 }
 
@@ -50,13 +285,30 @@ pub fn reload_system() -> Box<dyn Schedulable> {
 }
 
 pub fn render_system() -> Box<dyn Runnable> {
+    let mut has_warned_about_lights = false;
     SystemBuilder::new("render")
         .write_resource::<Renderer>()
         .read_resource::<CameraState>()
         .read_resource::<AppState>()
-        .with_query(<Read<Transform>>::query())
+        .write_resource::<CullingStats>()
+        .with_query(<(
+            Read<AssetInstance>,
+            TryRead<Transform>,
+            TryRead<AssetPose>,
+        )>::query())
+        .with_query(<(
+            Read<Light>,
+            Read<Transform>,
+            TryRead<AssetChild>,
+            TryRead<AssetNode>,
+        )>::query())
+        .read_component::<AssetInstance>()
+        .read_component::<AssetPose>()
         .build_thread_local(
-            move |_, mut world, (renderer, camera_state, app_state), query| {
+            move |_,
+                  mut world,
+                  (renderer, camera_state, app_state, culling_stats),
+                  (query, light_query)| {
                 let context = renderer.context.clone();
 
                 let current_frame_synchronization = renderer
@@ -93,15 +345,12 @@ pub fn render_system() -> Box<dyn Runnable> {
 
                 // Update UBOS
 
-                let projection = glm::perspective_zo(
+                let projection = camera_state.projection.matrix(
                     renderer
                         .vulkan_swapchain()
                         .swapchain
                         .properties()
                         .aspect_ratio(),
-                    90_f32.to_radians(),
-                    0.1_f32,
-                    1000_f32,
                 );
 
                 if let Some(skybox_data) = &renderer.skybox_pipeline_data.as_ref() {
@@ -125,51 +374,252 @@ pub fn render_system() -> Box<dyn Runnable> {
                 };
                 let ubos = [ubo];
 
-                for transform in query.iter(&mut world) {
-                    // TODO: Keep track of the global transform using the gltf document
-                    // and render meshes at the correct transform
-                    // TODO: Go through all assets
-                    let asset_transform = transform.translate * transform.rotate * transform.scale;
-                    let asset_index = 0;
-
-                    let asset = &renderer.assets[asset_index];
-                    asset.walk(|node_index, graph| {
-                        let global_transform =
-                            GltfAsset::calculate_global_transform(node_index, graph);
-                        if let Some(mesh) = graph[node_index].mesh.as_ref() {
-                            if let Some(pbr_data) = &renderer.pbr_pipeline_data.as_ref() {
-                                pbr_data.uniform_buffer.upload_to_buffer(
-                                    &ubos,
-                                    0,
-                                    std::mem::align_of::<UniformBufferObject>() as _,
-                                );
-
-                                let full_dynamic_ubo_size = (asset.number_of_meshes as u64
-                                    * pbr_data.dynamic_alignment)
-                                    as u64;
-
-                                let dynamic_ubo = DynamicUniformBufferObject {
-                                    model: asset_transform * global_transform,
+                let mut lights = Vec::new();
+                for (light, transform, asset_child, asset_node) in light_query.iter(world) {
+                    let mut light_transform =
+                        transform.translate * transform.rotate * transform.scale;
+
+                    // Lights spawned from an asset follow the pose of the entity they were spawned from
+                    if let (Some(asset_child), Some(asset_node)) = (asset_child, asset_node) {
+                        let node_transform = match world.get_component::<AssetPose>(asset_child.0) {
+                            Some(pose) => pose.node_transform(asset_node.0),
+                            None => world
+                                .get_component::<AssetInstance>(asset_child.0)
+                                .and_then(|instance| renderer.assets.get(instance.asset_index))
+                                .and_then(|asset| asset.rest_pose.node_transform(asset_node.0)),
+                        };
+                        match node_transform {
+                            Some(node_transform) => light_transform *= node_transform,
+                            None => continue,
+                        }
+                    }
+
+                    lights.push(ShaderLight::new(&light, &light_transform));
+                }
+                if lights.len() > MAX_LIGHTS {
+                    if !has_warned_about_lights {
+                        log::warn!(
+                            "There are {} lights, but only the first {} are rendered",
+                            lights.len(),
+                            MAX_LIGHTS
+                        );
+                        has_warned_about_lights = true;
+                    }
+                    lights.truncate(MAX_LIGHTS);
+                }
+
+                if let Some(pbr_data) = &renderer.pbr_pipeline_data.as_ref() {
+                    let light_buffer = &pbr_data.light_buffers[renderer.current_frame];
+                    let number_of_lights = [lights.len() as i32];
+                    light_buffer.upload_to_buffer(
+                        &number_of_lights,
+                        0,
+                        std::mem::align_of::<i32>() as _,
+                    );
+                    light_buffer.upload_to_buffer(
+                        &lights,
+                        LIGHT_BUFFER_HEADER_SIZE,
+                        std::mem::align_of::<ShaderLight>() as _,
+                    );
+                    light_buffer
+                        .flush(
+                            0,
+                            LIGHT_BUFFER_HEADER_SIZE
+                                + lights.len() * std::mem::size_of::<ShaderLight>(),
+                        )
+                        .expect("Failed to flush buffer!");
+                }
+
+                // The number of visible instances of each mesh, which decides what gets recorded
+                let mut visible_instances = renderer
+                    .assets
+                    .iter()
+                    .map(|asset| vec![VisibleInstances::default(); asset.number_of_meshes])
+                    .collect::<Vec<_>>();
+
+                if let Some(pbr_data) = &renderer.pbr_pipeline_data.as_ref() {
+                    let instance_buffer = &pbr_data.instance_buffers[renderer.current_frame];
+                    pbr_data.uniform_buffers[renderer.current_frame].upload_to_buffer(
+                        &ubos,
+                        0,
+                        std::mem::align_of::<UniformBufferObject>() as _,
+                    );
+
+                    // The transforms and poses of the entities drawing each asset, in instance order.
+                    // Entities without a pose of their own are drawn in the rest pose.
+                    let mut entities = renderer
+                        .assets
+                        .iter()
+                        .map(|asset| {
+                            (0..asset.number_of_instances)
+                                .map(|_| (glm::Mat4::identity(), None))
+                                .collect::<Vec<_>>()
+                        })
+                        .collect::<Vec<_>>();
+                    for (instance, transform, pose) in query.iter(&mut world) {
+                        let transform = transform.map_or_else(glm::Mat4::identity, |transform| {
+                            transform.translate * transform.rotate * transform.scale
+                        });
+                        // Instances are only out of range until the asset loading system's changes are applied
+                        if let Some(entity) = entities
+                            .get_mut(instance.asset_index)
+                            .and_then(|entities| entities.get_mut(instance.instance_index))
+                        {
+                            *entity = (transform, pose);
+                        }
+                    }
+
+                    // Mesh instances are culled in world space, before the y axis is flipped
+                    let flip = glm::scaling(&glm::vec3(1.0, -1.0, 1.0));
+                    let frustum = Frustum::from_matrix(&(projection * camera_state.view * flip));
+                    let mut stats = CullingStats::default();
+
+                    for ((asset, entities), visible_instances) in renderer
+                        .assets
+                        .iter()
+                        .zip(entities.iter())
+                        .zip(visible_instances.iter_mut())
+                    {
+                        if entities.is_empty() {
+                            continue;
+                        }
+
+                        asset.walk(|node_index, graph| {
+                            let mesh = match graph[node_index].mesh.as_ref() {
+                                Some(mesh) => mesh,
+                                None => return,
+                            };
+
+                            let node = &graph[node_index];
+                            let mut instances = Vec::new();
+                            let mut mirrored_instances = Vec::new();
+                            for (instance_index, (entity_transform, pose)) in
+                                entities.iter().enumerate()
+                            {
+                                let pose = pose.as_deref().unwrap_or(&asset.rest_pose);
+                                let weights = &pose.weights[node.index];
+                                let joint_offset = asset
+                                    .entity_joint_offset(renderer.current_frame, instance_index)
+                                    as i32;
+
+                                // Skinned meshes are positioned by their joints instead
+                                // of the transform of the node they are attached to
+                                let node_transform = if node.skin.is_some() {
+                                    glm::Mat4::identity()
+                                } else {
+                                    pose.global_transforms[node.index]
                                 };
-                                let ubos = [dynamic_ubo];
-                                let buffer = &pbr_data.dynamic_uniform_buffer;
-                                let offset =
-                                    (pbr_data.dynamic_alignment * mesh.mesh_id as u64) as usize;
-
-                                buffer.upload_to_buffer(&ubos, offset, pbr_data.dynamic_alignment);
-                                buffer
-                                    .flush(0, full_dynamic_ubo_size as _)
-                                    .expect("Failed to flush buffer!");
+
+                                // Skinned meshes are culled as a whole using the bounds of their
+                                // posed joints, which already include the instance transforms
+                                let is_skinned_mesh_visible = pose.node_bounding_boxes[node.index]
+                                    .filter(|_| node.skin.is_some())
+                                    .map(|bounding_box| {
+                                        frustum.intersects_box(
+                                            &bounding_box.transform(entity_transform),
+                                        )
+                                    });
+
+                                for instance in mesh.instance_transforms.iter() {
+                                    let model = entity_transform * node_transform * instance;
+                                    let is_visible = is_skinned_mesh_visible.unwrap_or_else(|| {
+                                        match mesh.bounding_box {
+                                            Some(bounding_box) => frustum
+                                                .intersects_box(&bounding_box.transform(&model)),
+                                            None => true,
+                                        }
+                                    });
+                                    if !is_visible {
+                                        continue;
+                                    }
+
+                                    // Mirrored instances are drawn with the opposite winding order
+                                    let instance =
+                                        ShaderInstance::new(model, weights, joint_offset);
+                                    if model.determinant() < 0.0 {
+                                        mirrored_instances.push(instance);
+                                    } else {
+                                        instances.push(instance);
+                                    }
+                                }
                             }
-                        }
+                            let visible_mesh_instances = VisibleInstances {
+                                counter_clockwise: instances.len() as u32,
+                                clockwise: mirrored_instances.len() as u32,
+                            };
+                            instances.append(&mut mirrored_instances);
+                            instance_buffer.upload_to_buffer(
+                                &instances,
+                                mesh.first_instance * std::mem::size_of::<ShaderInstance>(),
+                                std::mem::align_of::<ShaderInstance>() as _,
+                            );
+
+                            let number_of_instances =
+                                mesh.instance_transforms.len() * entities.len();
+                            stats.mesh_instances += number_of_instances;
+                            stats.visible_mesh_instances += instances.len();
+
+                            // Meshes without visible instances aren't recorded at all,
+                            // and each winding order is drawn separately
+                            visible_instances[mesh.mesh_id] = visible_mesh_instances;
+                            let number_of_groups = [
+                                visible_mesh_instances.counter_clockwise,
+                                visible_mesh_instances.clockwise,
+                            ]
+                            .iter()
+                            .filter(|count| **count > 0)
+                            .count();
+                            stats.draws += number_of_groups * mesh.primitives.len();
+                        });
+                    }
+
+                    let number_of_instance_transforms = renderer
+                        .assets
+                        .iter()
+                        .map(|asset| asset.number_of_instance_transforms())
+                        .sum::<usize>();
+                    instance_buffer
+                        .flush(
+                            0,
+                            number_of_instance_transforms * std::mem::size_of::<ShaderInstance>(),
+                        )
+                        .expect("Failed to flush buffer!");
+
+                    **culling_stats = stats;
 
-                        // TODO: Handle skins
-                    });
+                    // Every entity's joint matrices follow those of the entity before it,
+                    // in the part of the joint buffer that belongs to the current frame
+                    for (asset, entities) in renderer.assets.iter().zip(entities.iter()) {
+                        if asset.number_of_joints == 0 || entities.is_empty() {
+                            continue;
+                        }
+                        for (instance_index, (_, pose)) in entities.iter().enumerate() {
+                            let pose = pose.as_deref().unwrap_or(&asset.rest_pose);
+                            asset.joint_buffer.upload_to_buffer(
+                                &pose.joint_matrices,
+                                asset.entity_joint_offset(renderer.current_frame, instance_index)
+                                    * std::mem::size_of::<glm::Mat4>(),
+                                std::mem::align_of::<glm::Mat4>() as _,
+                            );
+                        }
+                        asset
+                            .joint_buffer
+                            .flush(
+                                asset.entity_joint_offset(renderer.current_frame, 0)
+                                    * std::mem::size_of::<glm::Mat4>(),
+                                entities.len()
+                                    * asset.number_of_joints
+                                    * std::mem::size_of::<glm::Mat4>(),
+                            )
+                            .expect("Failed to flush buffer!");
+                    }
                 }
 
+                renderer.record_command_buffer(image_index as usize, &visible_instances);
+
                 let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
                 renderer.command_pool.submit_command_buffer(
-                    image_index as usize,
+                    renderer.current_frame,
                     renderer.context.graphics_queue(),
                     &wait_stages,
                     &current_frame_synchronization,
@@ -195,25 +645,48 @@ pub fn render_system() -> Box<dyn Runnable> {
                     _ => {}
                 }
 
-                renderer.current_frame += (1 + renderer.current_frame)
+                renderer.current_frame = (1 + renderer.current_frame)
                     % SynchronizationSet::MAX_FRAMES_IN_FLIGHT as usize;
             },
         )
 }
 
-// TODO: Replace this with a system that modifies an animation state component
+// Evaluates the animation of every animated entity into a pose of its own
 pub fn animation_system() -> Box<dyn Schedulable> {
+    // The states that the entities' current poses were evaluated from
+    let mut evaluated_states: HashMap<Entity, AnimationState> = HashMap::new();
     SystemBuilder::new("animation_system")
-        .write_resource::<Renderer>()
-        .with_query(<Write<AnimationState>>::query())
-        .build(move |_, mut world, renderer, query| {
-            let animation_states = &mut query.iter(&mut world).collect::<Vec<_>>();
-            if animation_states.is_empty() {
-                return;
-            }
-            for animation in renderer.assets[0].animations.iter_mut() {
-                animation.time += 0.0005;
+        .read_resource::<Renderer>()
+        .read_resource::<DeltaTime>()
+        .with_query(<(
+            Read<AssetInstance>,
+            Write<AnimationState>,
+            TryRead<AssetPose>,
+        )>::query())
+        .build(move |commands, mut world, (renderer, delta_time), query| {
+            let mut states = HashMap::new();
+            for (entity, (instance, mut animation_state, pose)) in query.iter_entities(&mut world) {
+                let asset = match renderer.assets.get(instance.asset_index) {
+                    Some(asset) => asset,
+                    None => continue,
+                };
+
+                // Entities of assets without animations are drawn in the rest pose
+                if asset.animations.is_empty() {
+                    continue;
+                }
+
+                asset.advance_animation(&mut animation_state, delta_time.0 as f32);
+
+                // Paused and finished animations keep their pose until their state changes
+                let is_unchanged = evaluated_states.get(&entity) == Some(&*animation_state);
+                if pose.is_none() || !is_unchanged {
+                    if let Some(pose) = asset.animate(&animation_state) {
+                        commands.add_component(entity, pose);
+                    }
+                }
+                states.insert(entity, animation_state.clone());
             }
-            renderer.assets[0].animate();
+            evaluated_states = states;
         })
 }